@@ -8,9 +8,11 @@ use bevy::{
 };
 use rand::Rng;
 use std::f32::consts::FRAC_PI_2;
-use vleue_navigator::{NavMesh, VleueNavigatorPlugin};
-
-const HANDLE_TRIMESH_OPTIMIZED: Handle<NavMesh> = Handle::weak_from_u128(0);
+use vleue_navigator::{
+    agent::{AgentArrived, NavAgent},
+    pathfinding::ComputedPath,
+    NavMesh, VleueNavigatorPlugin,
+};
 
 fn main() {
     App::new()
@@ -35,7 +37,7 @@ fn main() {
             (
                 give_target_auto,
                 give_target_on_click,
-                move_object,
+                despawn_reached_target,
                 move_hover,
                 target_activity,
                 trigger_navmesh_visibility,
@@ -59,7 +61,9 @@ struct GltfHandle(Handle<Gltf>);
 struct CurrentMesh(Handle<NavMesh>);
 
 fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
-    commands.insert_resource(GltfHandle(asset_server.load("meshes/navmesh.glb")));
+    commands.insert_resource(GltfHandle(asset_server.load("meshes/level.navmesh.glb")));
+    // The same file, read by the `NavMesh` loader from its mesh named `navmesh`
+    commands.insert_resource(CurrentMesh(asset_server.load("meshes/level.navmesh.glb")));
 
     commands.insert_resource(AmbientLight {
         color: Color::SEA_GREEN,
@@ -123,26 +127,22 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
         },
         ..Default::default()
     });
-
-    commands.insert_resource(CurrentMesh(HANDLE_TRIMESH_OPTIMIZED));
 }
 
 fn check_textures(
     mut next_state: ResMut<NextState<AppState>>,
     gltf: ResMut<GltfHandle>,
+    current_mesh: Res<CurrentMesh>,
     asset_server: Res<AssetServer>,
 ) {
-    if let Some(LoadState::Loaded) = asset_server.get_load_state(gltf.id()) {
+    if [gltf.id().untyped(), current_mesh.0.id().untyped()]
+        .into_iter()
+        .all(|id| asset_server.get_load_state(id) == Some(LoadState::Loaded))
+    {
         next_state.set(AppState::Playing);
     }
 }
 
-#[derive(Component)]
-struct Path {
-    current: Vec3,
-    next: Vec<Vec3>,
-}
-
 #[derive(Component)]
 struct Object(Option<Entity>);
 
@@ -155,6 +155,7 @@ struct Hover(Vec2);
 #[derive(Component, Clone)]
 struct NavMeshDisp(Handle<NavMesh>);
 
+#[allow(clippy::too_many_arguments)]
 fn setup_scene(
     mut commands: Commands,
     gltf: Res<GltfHandle>,
@@ -162,7 +163,8 @@ fn setup_scene(
     gltf_meshes: Res<Assets<GltfMesh>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut navmeshes: ResMut<Assets<NavMesh>>,
+    navmeshes: Res<Assets<NavMesh>>,
+    current_mesh: Res<CurrentMesh>,
 ) {
     let mut material: StandardMaterial = Color::ALICE_BLUE.into();
     material.perceptual_roughness = 1.0;
@@ -220,20 +222,8 @@ fn setup_scene(
         }
     }
 
-    if let Some(gltf) = gltfs.get(gltf.id()) {
+    if let Some(navmesh) = navmeshes.get(&current_mesh.0) {
         {
-            let navmesh = vleue_navigator::NavMesh::from_bevy_mesh(
-                meshes
-                    .get(
-                        &gltf_meshes
-                            .get(&gltf.named_meshes["navmesh"])
-                            .unwrap()
-                            .primitives[0]
-                            .mesh,
-                    )
-                    .unwrap(),
-            );
-
             let mut material: StandardMaterial = Color::ANTIQUE_WHITE.into();
             material.unlit = true;
 
@@ -245,9 +235,8 @@ fn setup_scene(
                     visibility: Visibility::Hidden,
                     ..Default::default()
                 },
-                NavMeshDisp(HANDLE_TRIMESH_OPTIMIZED),
+                NavMeshDisp(current_mesh.0.clone()),
             ));
-            navmeshes.insert(HANDLE_TRIMESH_OPTIMIZED, navmesh);
         }
 
         commands
@@ -263,6 +252,10 @@ fn setup_scene(
                     ..Default::default()
                 },
                 Object(None),
+                NavAgent {
+                    speed: 10.0,
+                    ..default()
+                },
                 NotShadowCaster,
             ))
             .with_children(|object| {
@@ -283,7 +276,7 @@ fn setup_scene(
 
 fn give_target_auto(
     mut commands: Commands,
-    mut object_query: Query<(Entity, &Transform, &mut Object), Without<Path>>,
+    mut object_query: Query<(Entity, &Transform, &mut Object), Without<ComputedPath>>,
     navmeshes: Res<Assets<NavMesh>>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
@@ -291,37 +284,24 @@ fn give_target_auto(
 ) {
     for (entity, transform, mut object) in object_query.iter_mut() {
         let navmesh = navmeshes.get(&current_mesh.0).unwrap();
-        let mut x;
-        let mut z;
-        loop {
-            x = rand::thread_rng().gen_range(-50.0..50.0);
-            z = rand::thread_rng().gen_range(-25.0..25.0);
-
-            if navmesh.transformed_is_in_mesh(Vec3::new(x, 0.0, z)) {
-                break;
-            }
-        }
+        let Some(target) = navmesh.transformed_random_point(&mut rand::thread_rng()) else {
+            break;
+        };
 
-        let Some(path) = navmesh.transformed_path(transform.translation, Vec3::new(x, 0.0, z))
-        else {
+        let Some(path) = navmesh.transformed_path(transform.translation, target) else {
             break;
         };
-        if let Some((first, remaining)) = path.path.split_first() {
-            let mut remaining = remaining.to_vec();
-            remaining.reverse();
+        if !path.path.is_empty() {
             let target_id = commands
                 .spawn((
                     PbrBundle {
-                        mesh: meshes.add(Mesh::from(Sphere {
-                            radius: 0.5,
-                            ..default()
-                        })),
+                        mesh: meshes.add(Mesh::from(Sphere { radius: 0.5 })),
                         material: materials.add(StandardMaterial {
                             base_color: Color::RED,
                             emissive: Color::RED * 50.0,
                             ..default()
                         }),
-                        transform: Transform::from_xyz(x, 0.0, z),
+                        transform: Transform::from_translation(target),
                         ..Default::default()
                     },
                     NotShadowCaster,
@@ -340,15 +320,13 @@ fn give_target_auto(
                     });
                 })
                 .id();
-            commands.entity(entity).insert(Path {
-                current: first.clone(),
-                next: remaining,
-            });
+            commands.entity(entity).insert(ComputedPath(path));
             object.0 = Some(target_id);
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn give_target_on_click(
     mut commands: Commands,
     mut object_query: Query<(Entity, &Transform, &mut Object)>,
@@ -370,7 +348,7 @@ fn give_target_on_click(
             let denom = Vec3::Y.dot(ray.direction.into());
             let t = (Vec3::ZERO - ray.origin).dot(Vec3::Y) / denom;
             let target = ray.origin + ray.direction * t;
-            navmesh.transformed_is_in_mesh(target).then_some(target)
+            navmesh.transformed_closest_point_within(target, 5.0)
         })() else {
             return;
         };
@@ -379,16 +357,11 @@ fn give_target_on_click(
             let Some(path) = navmesh.transformed_path(transform.translation, target) else {
                 break;
             };
-            if let Some((first, remaining)) = path.path.split_first() {
-                let mut remaining = remaining.to_vec();
-                remaining.reverse();
+            if !path.path.is_empty() {
                 let target_id = commands
                     .spawn((
                         PbrBundle {
-                            mesh: meshes.add(Mesh::from(Sphere {
-                                radius: 0.5,
-                                ..default()
-                            })),
+                            mesh: meshes.add(Mesh::from(Sphere { radius: 0.5 })),
                             material: materials.add(StandardMaterial {
                                 base_color: Color::RED,
                                 emissive: Color::RED * 50.0,
@@ -413,10 +386,7 @@ fn give_target_on_click(
                         });
                     })
                     .id();
-                commands.entity(entity).insert(Path {
-                    current: first.clone(),
-                    next: remaining,
-                });
+                commands.entity(entity).insert(ComputedPath(path));
                 object.0 = Some(target_id);
             }
         }
@@ -426,20 +396,14 @@ fn give_target_on_click(
     }
 }
 
-fn move_object(
+fn despawn_reached_target(
     mut commands: Commands,
-    mut object_query: Query<(&mut Transform, &mut Path, Entity, &mut Object)>,
-    time: Res<Time>,
+    mut arrived: EventReader<AgentArrived>,
+    mut object_query: Query<&mut Object>,
 ) {
-    for (mut transform, mut target, entity, mut object) in object_query.iter_mut() {
-        let move_direction = target.current - transform.translation;
-        transform.translation += move_direction.normalize() * time.delta_seconds() * 10.0;
-        if transform.translation.distance(target.current) < 0.1 {
-            if let Some(next) = target.next.pop() {
-                target.current = next;
-            } else {
-                commands.entity(entity).remove::<Path>();
-                let target_entity = object.0.take().unwrap();
+    for AgentArrived { entity } in arrived.read() {
+        if let Ok(mut object) = object_query.get_mut(*entity) {
+            if let Some(target_entity) = object.0.take() {
                 commands.entity(target_entity).despawn_recursive();
             }
         }
@@ -1,8 +1,4 @@
-use std::{
-    collections::VecDeque,
-    sync::{Arc, RwLock},
-    time::Duration,
-};
+use std::{collections::VecDeque, time::Duration};
 
 use bevy::{
     core::TaskPoolThreadAssignmentPolicy,
@@ -10,13 +6,14 @@ use bevy::{
     math::Vec3Swizzles,
     prelude::*,
     sprite::MaterialMesh2dBundle,
-    tasks::AsyncComputeTaskPool,
-    utils::Instant,
     window::{PresentMode, PrimaryWindow, WindowResized},
 };
 use rand::prelude::*;
 
-use vleue_navigator::{NavMesh, VleueNavigatorPlugin};
+use vleue_navigator::{
+    pathfinding::{ComputedPath, PathFailed, PathRequest, PathSearchMode, PathTiming},
+    NavMesh, VleueNavigatorPlugin,
+};
 
 fn main() {
     App::new()
@@ -42,12 +39,12 @@ fn main() {
                         ..default()
                     },
                 }),
-            FrameTimeDiagnosticsPlugin::default(),
+            FrameTimeDiagnosticsPlugin,
             LogDiagnosticsPlugin::default(),
             VleueNavigatorPlugin,
         ))
         .init_resource::<Stats>()
-        .insert_resource(TaskMode::Blocking)
+        .insert_resource(PathSearchMode::Blocking)
         .insert_resource(DisplayMode::Line)
         .add_systems(Startup, setup)
         .add_systems(
@@ -55,8 +52,9 @@ fn main() {
             (
                 on_mesh_change,
                 go_somewhere,
-                compute_paths,
-                poll_path_tasks,
+                follow_computed_path,
+                no_path_found,
+                record_timings,
                 move_navigator,
                 display_path,
                 mode_change,
@@ -67,12 +65,6 @@ fn main() {
         .run();
 }
 
-#[derive(Debug, PartialEq, Eq, Clone, Copy, Resource)]
-enum TaskMode {
-    Async,
-    Blocking,
-}
-
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Resource)]
 enum DisplayMode {
     Line,
@@ -204,6 +196,7 @@ fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
     });
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_mesh_change(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
@@ -251,11 +244,6 @@ struct Navigator {
     color: Color,
 }
 
-#[derive(Component)]
-struct Target {
-    target: Vec2,
-}
-
 #[derive(Component)]
 struct Path {
     path: Vec<Vec2>,
@@ -316,107 +304,53 @@ fn spawn(
     }
 }
 
-#[derive(Default)]
-struct TaskResult {
-    path: Option<polyanya::Path>,
-    done: bool,
-    delay: f32,
-    duration: f32,
+fn follow_computed_path(
+    mut commands: Commands,
+    computed: Query<(Entity, &ComputedPath), Added<ComputedPath>>,
+) {
+    for (entity, computed) in &computed {
+        commands
+            .entity(entity)
+            .insert(Path {
+                path: computed.0.path.iter().map(|p| p.xy()).collect(),
+            })
+            .remove::<ComputedPath>();
+    }
 }
 
-#[derive(Component)]
-struct FindingPath(Arc<RwLock<TaskResult>>);
-
-fn compute_paths(
+fn no_path_found(
     mut commands: Commands,
-    with_target: Query<(Entity, &Target, &Transform), Changed<Target>>,
-    meshes: Res<Assets<NavMesh>>,
+    failed: Query<(Entity, &Transform), Added<PathFailed>>,
+    navmeshes: Res<Assets<NavMesh>>,
+    meshes: Res<Meshes>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
-    task_mode: Res<TaskMode>,
-    mesh: Res<Meshes>,
 ) {
-    let mesh = if let Some(mesh) = meshes.get(&mesh.aurora) {
-        mesh
-    } else {
+    let Some(navmesh) = navmeshes.get(&meshes.aurora) else {
         return;
     };
-    for (entity, target, transform) in &with_target {
-        let window = primary_window.single();
-        let factor = (window.width() / MESH_SIZE.x).min(window.height() / MESH_SIZE.y);
-
-        let in_mesh = transform.translation.truncate() / factor + MESH_SIZE / 2.0;
-
-        let to = target.target;
-        let mesh = mesh.clone();
-        let finding = FindingPath(Arc::new(RwLock::new(TaskResult::default())));
-        let writer = finding.0.clone();
-        let start = Instant::now();
-        let task_mode = *task_mode;
-        AsyncComputeTaskPool::get()
-            .spawn(async move {
-                let delay = (Instant::now() - start).as_secs_f32();
-                let path = if task_mode == TaskMode::Async {
-                    mesh.get_path(in_mesh, to).await
-                } else {
-                    mesh.path(in_mesh, to)
-                };
-                *writer.write().unwrap() = TaskResult {
-                    path,
-                    done: true,
-                    delay,
-                    duration: (Instant::now() - start).as_secs_f32() - delay,
-                };
-            })
-            .detach();
-        commands.entity(entity).insert(finding);
+    let window = primary_window.single();
+    let factor = (window.width() / MESH_SIZE.x).min(window.height() / MESH_SIZE.y);
+    for (entity, transform) in &failed {
+        if navmesh.is_in_mesh(transform.translation.xy() / factor + MESH_SIZE / 2.0) {
+            commands.entity(entity).remove::<PathFailed>();
+        } else {
+            commands.entity(entity).despawn();
+        }
     }
 }
 
 #[derive(Resource, Default)]
 struct Stats {
-    pathfinding_duration: VecDeque<f32>,
-    task_delay: VecDeque<f32>,
+    pathfinding_duration: VecDeque<Duration>,
+    task_delay: VecDeque<Duration>,
 }
 
-fn poll_path_tasks(
-    mut commands: Commands,
-    computing: Query<(Entity, &FindingPath, &Transform)>,
-    mut stats: ResMut<Stats>,
-    navmeshes: Res<Assets<NavMesh>>,
-    meshes: Res<Meshes>,
-    primary_window: Query<&Window, With<PrimaryWindow>>,
-) {
-    for (entity, task, transform) in &computing {
-        let mut task = task.0.write().unwrap();
-        if task.done {
-            stats.pathfinding_duration.push_front(task.duration);
-            stats.pathfinding_duration.truncate(100);
-            stats.task_delay.push_front(task.delay);
-            stats.task_delay.truncate(100);
-            if let Some(path) = task.path.take() {
-                commands
-                    .entity(entity)
-                    .insert(Path { path: path.path })
-                    .remove::<FindingPath>();
-            } else {
-                let window = primary_window.single();
-                let screen = Vec2::new(window.width(), window.height());
-                let factor = (screen.x / MESH_SIZE.x).min(screen.y / MESH_SIZE.y);
-
-                if !navmeshes
-                    .get(&meshes.aurora)
-                    .unwrap()
-                    .is_in_mesh(transform.translation.xy() / factor + MESH_SIZE / 2.0)
-                {
-                    commands.entity(entity).despawn();
-                }
-
-                commands
-                    .entity(entity)
-                    .remove::<FindingPath>()
-                    .remove::<Target>();
-            }
-        }
+fn record_timings(timings: Query<&PathTiming, Changed<PathTiming>>, mut stats: ResMut<Stats>) {
+    for timing in &timings {
+        stats.pathfinding_duration.push_front(timing.duration);
+        stats.pathfinding_duration.truncate(100);
+        stats.task_delay.push_front(timing.delay);
+        stats.task_delay.truncate(100);
     }
 }
 
@@ -438,7 +372,7 @@ fn move_navigator(
                 path.path.remove(0);
                 if path.path.is_empty() {
                     par_commands.command_scope(|mut commands| {
-                        commands.entity(entity).remove::<Path>().remove::<Target>();
+                        commands.entity(entity).remove::<Path>();
                     });
                 }
             }
@@ -469,25 +403,38 @@ fn display_path(
     }
 }
 
+#[allow(clippy::type_complexity)]
 fn go_somewhere(
     query: Query<
-        Entity,
+        (Entity, &Transform),
         (
             With<Navigator>,
             Without<Path>,
-            Without<FindingPath>,
-            Without<Target>,
+            Without<PathRequest>,
+            Without<PathFailed>,
         ),
     >,
     mut commands: Commands,
+    navmeshes: Res<Assets<NavMesh>>,
+    meshes: Res<Meshes>,
+    primary_window: Query<&Window, With<PrimaryWindow>>,
 ) {
+    let Some(navmesh) = navmeshes.get(&meshes.aurora) else {
+        return;
+    };
+    let window = primary_window.single();
+    let factor = (window.width() / MESH_SIZE.x).min(window.height() / MESH_SIZE.y);
     let mut rng = rand::thread_rng();
-    for navigator in &query {
-        let target = Vec2::new(
-            rng.gen_range(0.0..MESH_SIZE.x),
-            rng.gen_range(0.0..MESH_SIZE.y),
-        );
-        commands.entity(navigator).insert(Target { target: target });
+    for (navigator, transform) in &query {
+        let Some(target) = navmesh.random_point(&mut rng) else {
+            return;
+        };
+        let from = transform.translation.xy() / factor + MESH_SIZE / 2.0;
+        commands.entity(navigator).insert(PathRequest {
+            from: from.extend(0.0),
+            to: target.extend(0.0),
+            navmesh: meshes.aurora.clone_weak(),
+        });
     }
 }
 
@@ -497,7 +444,7 @@ fn update_ui(
     mut count: Local<usize>,
     stats: Res<Stats>,
     diagnostics: Res<DiagnosticsStore>,
-    task_mode: Res<TaskMode>,
+    search_mode: Res<PathSearchMode>,
     display_mode: Res<DisplayMode>,
 ) {
     let new_count = agents.iter().len();
@@ -513,37 +460,31 @@ fn update_ui(
 
     text.sections[5].value = format!(
         "{:?}\n",
-        Duration::from_secs_f32(
-            stats.pathfinding_duration.iter().sum::<f32>()
-                / (stats.pathfinding_duration.len().max(1) as f32)
-        ),
+        stats.pathfinding_duration.iter().sum::<Duration>()
+            / (stats.pathfinding_duration.len().max(1) as u32),
     );
     text.sections[7].value = format!(
         "{:?}\n",
-        Duration::from_secs_f32(
-            stats.task_delay.iter().sum::<f32>() / (stats.task_delay.len().max(1) as f32)
-        )
-    );
-    text.sections[9].value = format!("{:?}\n", *task_mode);
-    text.sections[11].value = format!(
-        "{}",
-        match *display_mode {
-            DisplayMode::Line => "hide lines",
-            DisplayMode::Nothing => "display lines",
-        }
+        stats.task_delay.iter().sum::<Duration>() / (stats.task_delay.len().max(1) as u32)
     );
+    text.sections[9].value = format!("{:?}\n", *search_mode);
+    text.sections[11].value = match *display_mode {
+        DisplayMode::Line => "hide lines",
+        DisplayMode::Nothing => "display lines",
+    }
+    .to_string();
     *count = new_count;
 }
 
 fn mode_change(
     keyboard_input: Res<ButtonInput<KeyCode>>,
-    mut task_mode: ResMut<TaskMode>,
+    mut search_mode: ResMut<PathSearchMode>,
     mut display_mode: ResMut<DisplayMode>,
 ) {
     if keyboard_input.just_pressed(KeyCode::Space) {
-        match *task_mode {
-            TaskMode::Async => *task_mode = TaskMode::Blocking,
-            TaskMode::Blocking => *task_mode = TaskMode::Async,
+        match *search_mode {
+            PathSearchMode::Async => *search_mode = PathSearchMode::Blocking,
+            PathSearchMode::Blocking => *search_mode = PathSearchMode::Async,
         }
     }
     if keyboard_input.just_pressed(KeyCode::KeyL) {
@@ -1,17 +1,16 @@
-use std::{
-    sync::{Arc, RwLock},
-    time::Duration,
-};
+use std::time::Duration;
 
 use bevy::{
     math::Vec3Swizzles,
     prelude::*,
     sprite::MaterialMesh2dBundle,
-    tasks::AsyncComputeTaskPool,
     window::{PrimaryWindow, WindowResized},
 };
 
-use vleue_navigator::{NavMesh, VleueNavigatorPlugin};
+use vleue_navigator::{
+    pathfinding::{ComputedPath, PathFailed, PathRequest},
+    NavMesh, VleueNavigatorPlugin,
+};
 
 fn main() {
     App::new()
@@ -33,8 +32,8 @@ fn main() {
                 on_mesh_change,
                 mesh_change,
                 on_click,
-                compute_paths,
-                poll_path_tasks,
+                follow_computed_path,
+                no_path_found,
                 move_navigator,
                 display_path,
             ),
@@ -125,6 +124,7 @@ fn setup(
     commands.insert_resource(AURORA);
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_mesh_change(
     mesh: Res<MeshDetails>,
     mut commands: Commands,
@@ -257,17 +257,12 @@ struct Navigator {
     speed: f32,
 }
 
-#[derive(Component)]
-struct Target {
-    target: Vec2,
-    navmesh: Handle<NavMesh>,
-}
-
 #[derive(Component)]
 struct Path {
     path: Vec<Vec2>,
 }
 
+#[allow(clippy::too_many_arguments)]
 fn on_click(
     mouse_button_input: Res<ButtonInput<MouseButton>>,
     primary_window: Query<&Window, With<PrimaryWindow>>,
@@ -275,7 +270,7 @@ fn on_click(
     mesh: Res<MeshDetails>,
     meshes: Res<Meshes>,
     mut commands: Commands,
-    query: Query<Entity, With<Navigator>>,
+    query: Query<(Entity, &Transform), With<Navigator>>,
     navmeshes: Res<Assets<NavMesh>>,
 ) {
     if mouse_button_input.just_pressed(MouseButton::Left) {
@@ -298,10 +293,12 @@ fn on_click(
                 .map(|mesh| mesh.is_in_mesh(in_mesh))
                 .unwrap_or_default()
             {
-                if let Ok(navigator) = query.get_single() {
+                if let Ok((navigator, transform)) = query.get_single() {
                     info!("going to {}", in_mesh);
-                    commands.entity(navigator).insert(Target {
-                        target: in_mesh,
+                    let from = transform.translation.truncate() / factor + mesh.size / 2.0;
+                    commands.entity(navigator).insert(PathRequest {
+                        from: from.extend(0.0),
+                        to: in_mesh.extend(0.0),
                         navmesh: match mesh.mesh {
                             CurrentMesh::Simple => meshes.simple.clone_weak(),
                             CurrentMesh::Arena => meshes.arena.clone_weak(),
@@ -334,50 +331,24 @@ fn on_click(
     }
 }
 
-#[derive(Component)]
-struct FindingPath(Arc<RwLock<(Option<polyanya::Path>, bool)>>);
-
-fn compute_paths(
+fn follow_computed_path(
     mut commands: Commands,
-    with_target: Query<(Entity, &Target, &Transform), Changed<Target>>,
-    meshes: Res<Assets<NavMesh>>,
-    mesh: Res<MeshDetails>,
-    primary_window: Query<&Window, With<PrimaryWindow>>,
+    computed: Query<(Entity, &ComputedPath), Added<ComputedPath>>,
 ) {
-    let window = primary_window.single();
-    let factor = (window.width() / mesh.size.x).min(window.height() / mesh.size.y);
-
-    for (entity, target, transform) in &with_target {
-        let in_mesh = transform.translation.truncate() / factor + mesh.size / 2.0;
-        let mesh = meshes.get(&target.navmesh).unwrap();
-
-        let to = target.target;
-        let mesh = mesh.clone();
-        let finding = FindingPath(Arc::new(RwLock::new((None, false))));
-        let writer = finding.0.clone();
-        AsyncComputeTaskPool::get()
-            .spawn(async move {
-                let path = mesh.path(in_mesh, to);
-                *writer.write().unwrap() = (path, true);
+    for (entity, computed) in &computed {
+        commands
+            .entity(entity)
+            .insert(Path {
+                path: computed.0.path.iter().map(|p| p.xy()).collect(),
             })
-            .detach();
-        commands.entity(entity).insert(finding);
+            .remove::<ComputedPath>();
     }
 }
 
-fn poll_path_tasks(mut commands: Commands, computing: Query<(Entity, &FindingPath)>) {
-    for (entity, task) in &computing {
-        let mut task = task.0.write().unwrap();
-        if task.1 {
-            if let Some(path) = task.0.take() {
-                commands
-                    .entity(entity)
-                    .insert(Path { path: path.path })
-                    .remove::<FindingPath>();
-            } else {
-                info!("no path found");
-            }
-        }
+fn no_path_found(mut commands: Commands, failed: Query<Entity, Added<PathFailed>>) {
+    for entity in &failed {
+        info!("no path found");
+        commands.entity(entity).remove::<PathFailed>();
     }
 }
 
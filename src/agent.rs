@@ -0,0 +1,305 @@
+//! An agent that follows a [`ComputedPath`], driven by [`VleueNavigatorPlugin`](crate::VleueNavigatorPlugin)
+
+use bevy::{math::Affine3A, prelude::*};
+
+use crate::{pathfinding::ComputedPath, TransformedPath};
+
+/// An entity that moves along its [`ComputedPath`] by updating its [`Transform`].
+///
+/// Paths are in world coordinates. For an agent with a [`Parent`], its position is converted
+/// through the [`GlobalTransform`] of the parent, as it was at the end of the previous frame.
+///
+/// Waypoints are consumed from the path as they are reached, along with the off-mesh links
+/// leading to them. Once the destination is reached,
+/// the [`ComputedPath`] is removed and an [`AgentArrived`] event is sent. If the agent doesn't get
+/// closer to its next waypoint for [`NavAgent::stuck_timeout`] seconds, for example because
+/// something else is moving it, the [`ComputedPath`] is removed and an [`AgentStuck`] event is sent.
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub struct NavAgent {
+    /// Speed of the agent, in world units per second.
+    pub speed: f32,
+    /// Distance to the destination under which the agent is considered arrived.
+    pub arrival_radius: f32,
+    /// Duration in seconds without progress toward the next waypoint before the agent is considered stuck.
+    pub stuck_timeout: f32,
+}
+
+impl Default for NavAgent {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            arrival_radius: 0.1,
+            stuck_timeout: 1.0,
+        }
+    }
+}
+
+/// Event sent when a [`NavAgent`] reached the end of its [`ComputedPath`].
+#[derive(Event, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AgentArrived {
+    /// The agent that arrived.
+    pub entity: Entity,
+}
+
+/// Event sent when a [`NavAgent`] stopped making progress along its [`ComputedPath`].
+#[derive(Event, Debug, Clone, Copy, PartialEq)]
+pub struct AgentStuck {
+    /// The agent that is stuck.
+    pub entity: Entity,
+    /// The waypoint the agent was trying to reach.
+    pub waypoint: Vec3,
+}
+
+/// Progress of an agent toward its next waypoint, used to detect when it's stuck.
+#[derive(Component, Debug, Clone, Copy)]
+pub(crate) struct AgentProgress {
+    waypoint: Vec3,
+    closest: f32,
+    since: f32,
+}
+
+/// What [`move_agents`] reads and changes on each agent
+type AgentData<'a> = (
+    Entity,
+    &'a NavAgent,
+    &'a mut Transform,
+    &'a mut ComputedPath,
+    Option<&'a mut AgentProgress>,
+    Option<&'a Parent>,
+);
+
+pub(crate) fn move_agents(
+    mut commands: Commands,
+    mut agents: Query<AgentData>,
+    parents: Query<&GlobalTransform>,
+    time: Res<Time>,
+    mut arrived: EventWriter<AgentArrived>,
+    mut stuck: EventWriter<AgentStuck>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, agent, mut transform, mut path, progress, parent) in &mut agents {
+        // Checked before moving, which changes the path. Changes made by this system on the
+        // previous frames are not seen, so this is only when the path was added or replaced.
+        let new_path = path.is_changed();
+        // Paths are in world coordinates, and the transform is relative to the parent
+        let to_world = parent
+            .and_then(|parent| parents.get(parent.get()).ok())
+            .map_or(Affine3A::IDENTITY, GlobalTransform::affine);
+        let mut position = to_world.transform_point3(transform.translation);
+        let mut step = agent.speed * delta;
+        while let Some(next) = path.0.path.first().copied() {
+            let distance = position.distance(next);
+            if distance <= step {
+                position = next;
+                step -= distance;
+                pop_waypoint(&mut path.0);
+            } else if path.0.path.len() == 1 && distance <= agent.arrival_radius {
+                pop_waypoint(&mut path.0);
+            } else {
+                let direction = (next - position) / distance;
+                position += direction * step;
+                break;
+            }
+        }
+        let local = to_world.inverse().transform_point3(position);
+        if local != transform.translation {
+            transform.translation = local;
+        }
+
+        let Some(next) = path.0.path.first().copied() else {
+            commands
+                .entity(entity)
+                .remove::<(ComputedPath, AgentProgress)>();
+            arrived.send(AgentArrived { entity });
+            continue;
+        };
+
+        let distance = position.distance(next);
+        match progress {
+            Some(mut progress) if progress.waypoint == next && !new_path => {
+                if distance < progress.closest {
+                    progress.closest = distance;
+                    progress.since = 0.0;
+                } else {
+                    progress.since += delta;
+                    if progress.since > agent.stuck_timeout {
+                        commands
+                            .entity(entity)
+                            .remove::<(ComputedPath, AgentProgress)>();
+                        stuck.send(AgentStuck {
+                            entity,
+                            waypoint: next,
+                        });
+                    }
+                }
+            }
+            _ => {
+                commands.entity(entity).insert(AgentProgress {
+                    waypoint: next,
+                    closest: distance,
+                    since: 0.0,
+                });
+            }
+        }
+    }
+}
+
+/// Removes the first waypoint of a path, keeping the indices of the links taken in sync.
+fn pop_waypoint(path: &mut TransformedPath) {
+    path.path.remove(0);
+    path.links.retain(|link| link.waypoint > 0);
+    for link in &mut path.links {
+        link.waypoint -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::TimeUpdateStrategy;
+
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct Received(Vec<AgentArrived>, Vec<AgentStuck>);
+
+    fn record_events(
+        mut received: ResMut<Received>,
+        mut arrived: EventReader<AgentArrived>,
+        mut stuck: EventReader<AgentStuck>,
+    ) {
+        received.0.extend(arrived.read());
+        received.1.extend(stuck.read());
+    }
+
+    fn computed_path(path: Vec<Vec3>) -> ComputedPath {
+        ComputedPath(TransformedPath {
+            length: 0.0,
+            path,
+            links: vec![],
+        })
+    }
+
+    fn agent_app(agent: NavAgent, path: Vec<Vec3>) -> (App, Entity) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+                100,
+            )))
+            .init_resource::<Received>()
+            .add_event::<AgentArrived>()
+            .add_event::<AgentStuck>()
+            .add_systems(Update, (move_agents, record_events).chain());
+        let entity = app
+            .world
+            .spawn((agent, Transform::default(), computed_path(path)))
+            .id();
+        (app, entity)
+    }
+
+    fn run_agent(agent: NavAgent, path: Vec<Vec3>) -> (App, Entity) {
+        let (mut app, entity) = agent_app(agent, path);
+        for _ in 0..30 {
+            app.update();
+        }
+        (app, entity)
+    }
+
+    #[test]
+    fn agent_follows_path_to_destination() {
+        let agent = NavAgent {
+            speed: 10.0,
+            ..default()
+        };
+        let (app, entity) = run_agent(agent, vec![Vec3::X, Vec3::new(1.0, 1.0, 0.0)]);
+
+        let entity_ref = app.world.entity(entity);
+        assert_eq!(
+            entity_ref.get::<Transform>().unwrap().translation,
+            Vec3::new(1.0, 1.0, 0.0)
+        );
+        assert!(!entity_ref.contains::<ComputedPath>());
+        let received = app.world.resource::<Received>();
+        assert_eq!(received.0, vec![AgentArrived { entity }]);
+        assert!(received.1.is_empty());
+    }
+
+    #[test]
+    fn agent_with_a_parent_follows_path_in_world_coordinates() {
+        let agent = NavAgent {
+            speed: 10.0,
+            ..default()
+        };
+        let (mut app, entity) = agent_app(agent, vec![Vec3::new(12.0, 0.0, 0.0)]);
+        let parent = app
+            .world
+            .spawn(GlobalTransform::from(
+                Transform::from_xyz(10.0, 0.0, 0.0).with_scale(Vec3::splat(2.0)),
+            ))
+            .id();
+        app.world.entity_mut(entity).set_parent(parent);
+        for _ in 0..30 {
+            app.update();
+        }
+
+        assert_eq!(
+            app.world
+                .entity(entity)
+                .get::<Transform>()
+                .unwrap()
+                .translation,
+            Vec3::new(1.0, 0.0, 0.0)
+        );
+        assert_eq!(
+            app.world.resource::<Received>().0,
+            vec![AgentArrived { entity }]
+        );
+    }
+
+    #[test]
+    fn agent_not_moving_is_stuck() {
+        let agent = NavAgent {
+            speed: 0.0,
+            ..default()
+        };
+        let (app, entity) = run_agent(agent, vec![Vec3::X]);
+
+        assert!(!app.world.entity(entity).contains::<ComputedPath>());
+        let received = app.world.resource::<Received>();
+        assert!(received.0.is_empty());
+        assert_eq!(
+            received.1,
+            vec![AgentStuck {
+                entity,
+                waypoint: Vec3::X
+            }]
+        );
+    }
+
+    #[test]
+    fn replaced_path_restarts_stuck_detection() {
+        let agent = NavAgent {
+            speed: 0.0,
+            ..default()
+        };
+        let (mut app, entity) = agent_app(agent, vec![Vec3::X]);
+        for _ in 0..8 {
+            app.update();
+        }
+        app.world
+            .entity_mut(entity)
+            .insert(computed_path(vec![Vec3::X]));
+        for _ in 0..8 {
+            app.update();
+        }
+
+        assert!(app.world.entity(entity).contains::<ComputedPath>());
+        assert!(app.world.resource::<Received>().1.is_empty());
+
+        for _ in 0..8 {
+            app.update();
+        }
+        assert_eq!(app.world.resource::<Received>().1.len(), 1);
+    }
+}
@@ -0,0 +1,1039 @@
+//! Area types tagging parts of a [`NavMesh`], with a cost multiplier for paths going through them
+
+use std::{cmp::Ordering, collections::BinaryHeap, iter, sync::Arc, task::Poll};
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{MeshVertexAttribute, VertexAttributeValues},
+        render_resource::VertexFormat,
+    },
+    tasks::futures_lite::future::yield_now,
+    utils::HashMap,
+};
+use geo::{BooleanOps, Coord, EuclideanLength, Intersects, LineString, MultiLineString, Point};
+use itertools::Itertools;
+use polyanya::Path;
+
+use crate::{
+    builder::{mesh_area, to_polygon, triangulate, union_all},
+    spatial::SpatialIndex,
+    NavMesh, NavMeshBuildError, TransformedPath,
+};
+
+/// The default area type, covering every part of a [`NavMesh`] not tagged with another area type.
+pub const DEFAULT_AREA: u8 = 0;
+
+/// Vertex attribute setting the area type of triangles when building a [`NavMesh`] from a Bevy
+/// [`Mesh`]. The area type of a triangle is the value shared by at least two of its vertices, or
+/// the value of its first vertex if all three are different.
+///
+/// Values are stored as floats so that they can be exported in a glTF file, as the `_NAV_AREA`
+/// attribute. They must be whole numbers from `0` to `255`, otherwise building the [`NavMesh`]
+/// fails with [`NavMeshBuildError::UnsupportedAttributeFormat`].
+pub const ATTRIBUTE_NAV_AREA: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_NavArea", 987_542_901, VertexFormat::Float32);
+
+impl NavMesh {
+    /// Tags the part of the mesh inside `polygon` with the area type `area`, replacing the area type
+    /// previously set there. The polygon is in mesh coordinates.
+    pub fn set_area(&mut self, area: u8, polygon: &[Vec2]) {
+        let region = union_all([to_polygon(polygon)]);
+        let areas = Arc::make_mut(&mut self.areas);
+        for other in areas.values_mut() {
+            *other = other.difference(&region);
+        }
+        if area != DEFAULT_AREA {
+            let merged = match areas.remove(&area) {
+                Some(existing) => existing.union(&region),
+                None => region,
+            };
+            areas.insert(area, merged);
+        }
+        areas.retain(|_, region| !region.0.is_empty());
+        self.filtered = Default::default();
+        self.costed = Default::default();
+        self.walks = Default::default();
+    }
+
+    /// The area type at `point`, in mesh coordinates.
+    pub fn area_at(&self, point: Vec2) -> u8 {
+        let point = Point::new(point.x, point.y);
+        self.areas
+            .iter()
+            .find(|(_, region)| region.intersects(&point))
+            .map_or(DEFAULT_AREA, |(area, _)| *area)
+    }
+
+    /// Sets the cost multiplier of paths going through an area type. Defaults to `1.0`.
+    ///
+    /// Paths going through areas of different costs are approximations, see [`NavMesh::path`].
+    ///
+    /// Returns [`NavMeshBuildError::InvalidSetting`] if `cost` is not a finite positive number.
+    pub fn set_area_cost(&mut self, area: u8, cost: f32) -> Result<(), NavMeshBuildError> {
+        if !(cost.is_finite() && cost > 0.0) {
+            return Err(NavMeshBuildError::InvalidSetting("cost"));
+        }
+        self.area_costs.insert(area, cost);
+        self.walks = Default::default();
+        Ok(())
+    }
+
+    /// The cost multiplier of paths going through an area type.
+    pub fn area_cost(&self, area: u8) -> f32 {
+        self.area_costs.get(&area).copied().unwrap_or(1.0)
+    }
+
+    /// The cost of a path starting at `from`, its length in each area type multiplied by the cost
+    /// of that area type.
+    pub fn path_cost(&self, from: Vec2, path: &Path) -> f32 {
+        let line = MultiLineString(vec![LineString(
+            iter::once(from)
+                .chain(path.path.iter().copied())
+                .map(|point| Coord {
+                    x: point.x,
+                    y: point.y,
+                })
+                .collect(),
+        )]);
+        let base = self.area_cost(DEFAULT_AREA);
+        self.areas
+            .iter()
+            .map(|(area, region)| {
+                region.clip(&line, false).euclidean_length() * (self.area_cost(*area) - base)
+            })
+            .sum::<f32>()
+            + line.euclidean_length() * base
+    }
+
+    /// Get a path between two points, not going through the area types excluded by `filter`.
+    ///
+    /// Paths avoid costly areas, see [`NavMesh::set_area_cost`].
+    ///
+    /// With walkable area types of different costs, the search runs on a mesh split along the
+    /// areas and only samples a few points on each edge it crosses, so the path found can cost
+    /// slightly more than the cheapest one. With a single cost, it's the shortest path.
+    pub fn filtered_path(&self, from: Vec2, to: Vec2, filter: &NavQueryFilter) -> Option<Path> {
+        self.linked_path(from, to, &filter.excluded)
+            .map(|(path, _)| path)
+    }
+
+    /// Get a path between two points, not going through the area types excluded by `filter`.
+    ///
+    /// Inputs and results are transformed using the [`NavMesh::transform`]
+    pub fn transformed_filtered_path(
+        &self,
+        from: Vec3,
+        to: Vec3,
+        filter: &NavQueryFilter,
+    ) -> Option<TransformedPath> {
+        let inner_from = self.transform.transform_point(from).xy();
+        let inner_to = self.transform.transform_point(to).xy();
+        let path = self.linked_path(inner_from, inner_to, &filter.excluded);
+        path.map(|(path, links)| self.transform_path(path, links, inner_from))
+    }
+
+    /// Finds the path with the lowest cost between two points, without the `excluded` area types.
+    ///
+    /// When all the area types that can be walked have the same cost, this is the shortest path
+    /// found by polyanya. Otherwise, the search is done on a mesh split along the area types, see
+    /// [`CostedMesh`], and the path found may cost a little more than the cheapest one.
+    pub(crate) fn cheapest_path(&self, from: Vec2, to: Vec2, excluded: &[u8]) -> Option<Path> {
+        if self.has_single_cost(excluded) {
+            return self.filtered_mesh(excluded)?.path(from, to);
+        }
+        self.costed_mesh(excluded)?.path(from, to, self)
+    }
+
+    /// Same as [`NavMesh::cheapest_path`], yielding regularly during the search.
+    pub(crate) async fn get_cheapest_path(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        excluded: &[u8],
+    ) -> Option<Path> {
+        if self.has_single_cost(excluded) {
+            return self.filtered_mesh(excluded)?.get_path(from, to).await;
+        }
+        self.costed_mesh(excluded)?.get_path(from, to, self).await
+    }
+
+    /// Checks if all the area types that can be walked, without the `excluded` ones, have the
+    /// same cost.
+    fn has_single_cost(&self, excluded: &[u8]) -> bool {
+        self.areas
+            .keys()
+            .chain([&DEFAULT_AREA])
+            .filter(|area| !excluded.contains(area))
+            .map(|area| self.area_cost(*area))
+            .all_equal()
+    }
+
+    /// The mesh without the given area types, built the first time it's needed.
+    ///
+    /// `excluded` must be sorted. Returns `None` if nothing is left.
+    pub(crate) fn filtered_mesh(&self, excluded: &[u8]) -> Option<Arc<polyanya::Mesh>> {
+        if excluded.is_empty() {
+            return Some(self.mesh.clone());
+        }
+        if let Some(mesh) = self.filtered.read().unwrap().get(excluded) {
+            return mesh.clone();
+        }
+
+        let regions = |removed: bool| {
+            union_all(
+                self.areas
+                    .iter()
+                    .filter(|(area, _)| excluded.contains(area) == removed)
+                    .flat_map(|(_, region)| region.0.iter().cloned()),
+            )
+        };
+        // The default area covers everything that's not in another area
+        let walkable = if excluded.contains(&DEFAULT_AREA) {
+            mesh_area(&self.mesh).intersection(&regions(false))
+        } else {
+            mesh_area(&self.mesh).difference(&regions(true))
+        };
+        let mesh = triangulate(&walkable, 0.0).ok().map(|mut mesh| {
+            mesh.set_delta(self.mesh.delta());
+            Arc::new(mesh)
+        });
+        self.filtered
+            .write()
+            .unwrap()
+            .insert(excluded.to_vec(), mesh.clone());
+        mesh
+    }
+
+    /// The mesh split along the area types, without the given ones, built the first time it's
+    /// needed.
+    ///
+    /// `excluded` must be sorted. Returns `None` if nothing is left.
+    pub(crate) fn costed_mesh(&self, excluded: &[u8]) -> Option<Arc<CostedMesh>> {
+        if let Some(mesh) = self.costed.read().unwrap().get(excluded) {
+            return mesh.clone();
+        }
+
+        let outline = mesh_area(&self.mesh);
+        let tagged = union_all(
+            self.areas
+                .values()
+                .flat_map(|region| region.0.iter().cloned()),
+        );
+        let pieces = self
+            .areas
+            .iter()
+            .map(|(area, region)| (*area, outline.intersection(region)))
+            .chain([(DEFAULT_AREA, outline.difference(&tagged))])
+            .filter(|(area, _)| !excluded.contains(area))
+            .filter_map(|(area, piece)| Some((area, triangulate(&piece, 0.0).ok()?)))
+            .collect::<Vec<_>>();
+        let mesh = (!pieces.is_empty()).then(|| Arc::new(CostedMesh::new(pieces)));
+        self.costed
+            .write()
+            .unwrap()
+            .insert(excluded.to_vec(), mesh.clone());
+        mesh
+    }
+}
+
+/// Points closer than this to a straight line between their neighbours are removed from paths.
+const STRAIGHT_TOLERANCE: f32 = 1e-4;
+
+/// Number of polygons searched by [`CostedMesh::get_path`] before yielding.
+const SEARCH_STEPS_PER_POLL: usize = 16;
+
+/// A [`NavMesh`] split along the boundaries of its area types, so that each polygon has a single
+/// cost, to search for the paths with the lowest cost.
+///
+/// Polygons are searched with A*, going through their shared edges, or portals. The portals
+/// crossed are then refined: the path is straightened between the portals where the cost changes,
+/// and the points on those portals are moved to lower the cost.
+///
+/// This is an approximation: the search only crosses each portal at its middle and at its points
+/// closest to the current point and to the destination, so it can pick a corridor that is not the
+/// cheapest one, and refining only lowers the cost within that corridor.
+#[derive(Debug)]
+pub(crate) struct CostedMesh {
+    polygons: Vec<CostedPolygon>,
+    /// Polygons by their bounding box
+    index: SpatialIndex,
+}
+
+#[derive(Debug)]
+struct CostedPolygon {
+    vertices: Vec<Vec2>,
+    area: u8,
+    portals: Vec<Portal>,
+}
+
+/// An edge shared with another polygon, with its ends as seen when leaving through it.
+#[derive(Debug, Clone, Copy)]
+struct Portal {
+    to: usize,
+    left: Vec2,
+    right: Vec2,
+}
+
+/// A point reached by the search, after crossing a portal into `polygon`.
+#[derive(Debug, Clone, Copy)]
+struct Reached {
+    polygon: usize,
+    point: Vec2,
+    cost: f32,
+    previous: Option<usize>,
+    portal: Option<Portal>,
+    arrived: bool,
+}
+
+/// An entry of the A* queue, ordered by lowest estimated cost first.
+#[derive(Debug, PartialEq)]
+struct Candidate {
+    estimate: f32,
+    reached: usize,
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate)
+    }
+}
+
+impl CostedPolygon {
+    fn portal(&self, to: usize, a: Vec2, b: Vec2) -> Portal {
+        let center = self.vertices.iter().sum::<Vec2>() / self.vertices.len() as f32;
+        let middle = (a + b) / 2.0;
+        if (middle - center).perp_dot(a - middle) > 0.0 {
+            Portal {
+                to,
+                left: a,
+                right: b,
+            }
+        } else {
+            Portal {
+                to,
+                left: b,
+                right: a,
+            }
+        }
+    }
+
+    /// Distance from `point` to the polygon, `0.0` if it's inside.
+    fn distance(&self, point: Vec2) -> f32 {
+        let edges = || self.vertices.iter().copied().circular_tuple_windows();
+        let sides = edges().map(|(a, b): (Vec2, Vec2)| (b - a).perp_dot(point - a));
+        if sides.clone().all(|side| side >= 0.0) || sides.clone().all(|side| side <= 0.0) {
+            return 0.0;
+        }
+        edges()
+            .map(|(a, b)| point.distance(closest_on_segment(point, a, b)))
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+impl CostedMesh {
+    /// Gathers the meshes of each area type, connecting them where their edges overlap.
+    fn new(pieces: Vec<(u8, polyanya::Mesh)>) -> CostedMesh {
+        let mut polygons: Vec<CostedPolygon> = vec![];
+        // Edges not shared inside their piece, with their piece and polygon
+        let mut borders = vec![];
+        for (piece, (area, mesh)) in pieces.into_iter().enumerate() {
+            let mut edges = HashMap::new();
+            for polygon in &mesh.polygons {
+                let index = polygons.len();
+                polygons.push(CostedPolygon {
+                    vertices: polygon
+                        .vertices
+                        .iter()
+                        .map(|vertex| mesh.vertices[*vertex as usize].coords)
+                        .collect(),
+                    area,
+                    portals: vec![],
+                });
+                for (a, b) in polygon.vertices.iter().copied().circular_tuple_windows() {
+                    match edges.remove(&(a.min(b), a.max(b))) {
+                        Some(other) => {
+                            let (a, b) = (
+                                mesh.vertices[a as usize].coords,
+                                mesh.vertices[b as usize].coords,
+                            );
+                            connect(&mut polygons, index, other, a, b);
+                        }
+                        None => {
+                            edges.insert((a.min(b), a.max(b)), index);
+                        }
+                    }
+                }
+            }
+            borders.extend(edges.into_iter().map(|((a, b), polygon)| {
+                let (a, b) = (
+                    mesh.vertices[a as usize].coords,
+                    mesh.vertices[b as usize].coords,
+                );
+                (piece, polygon, a, b)
+            }));
+        }
+
+        // Only borders close to each other can overlap
+        let tolerance = Vec2::splat(STRAIGHT_TOLERANCE);
+        let border_index = SpatialIndex::new(
+            &borders
+                .iter()
+                .map(|(_, _, a, b)| (a.min(*b) - tolerance, a.max(*b) + tolerance))
+                .collect::<Vec<_>>(),
+        );
+        for (i, (piece, polygon, a, b)) in borders.iter().enumerate() {
+            let close = border_index
+                .within(a.min(*b) - tolerance, a.max(*b) + tolerance)
+                .filter(|j| *j > i)
+                .sorted_unstable()
+                .dedup();
+            for (other_piece, other, c, d) in close.map(|j| &borders[j]) {
+                if piece == other_piece {
+                    continue;
+                }
+                if let Some((start, end)) = overlap(*a, *b, *c, *d) {
+                    connect(&mut polygons, *polygon, *other, start, end);
+                }
+            }
+        }
+        let bounds = polygons
+            .iter()
+            .map(|polygon| {
+                polygon
+                    .vertices
+                    .iter()
+                    .fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), point| {
+                        (min.min(*point), max.max(*point))
+                    })
+            })
+            .collect::<Vec<_>>();
+        CostedMesh {
+            index: SpatialIndex::new(&bounds),
+            polygons,
+        }
+    }
+
+    /// The polygon containing `point`, or the closest one if it's at most `tolerance` away.
+    fn locate(&self, point: Vec2, tolerance: f32) -> Option<usize> {
+        self.index
+            .within(point - tolerance, point + tolerance)
+            .map(|index| (index, self.polygons[index].distance(point)))
+            .filter(|(_, distance)| *distance <= tolerance)
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Finds the path with the lowest cost between two points, with the area costs of `navmesh`.
+    pub(crate) fn path(&self, from: Vec2, to: Vec2, navmesh: &NavMesh) -> Option<Path> {
+        let mut search = CostedSearch::new(self, from, to, navmesh)?;
+        loop {
+            if let Poll::Ready(path) = search.advance(SEARCH_STEPS_PER_POLL) {
+                return path;
+            }
+        }
+    }
+
+    /// Same as [`CostedMesh::path`], yielding regularly so that it doesn't block other tasks for
+    /// too long.
+    pub(crate) async fn get_path(&self, from: Vec2, to: Vec2, navmesh: &NavMesh) -> Option<Path> {
+        let mut search = CostedSearch::new(self, from, to, navmesh)?;
+        loop {
+            if let Poll::Ready(path) = search.advance(SEARCH_STEPS_PER_POLL) {
+                return path;
+            }
+            yield_now().await;
+        }
+    }
+}
+
+/// An A* search on a [`CostedMesh`], that can be run a few steps at a time.
+struct CostedSearch<'a> {
+    mesh: &'a CostedMesh,
+    navmesh: &'a NavMesh,
+    from: Vec2,
+    to: Vec2,
+    goal: usize,
+    /// Lowest cost of the polygons, to weight the estimate
+    cheapest: f32,
+    reached: Vec<Reached>,
+    /// Lowest cost found to cross each sample of each portal
+    best: HashMap<(usize, usize, usize), f32>,
+    queue: BinaryHeap<Candidate>,
+}
+
+impl<'a> CostedSearch<'a> {
+    fn new(mesh: &'a CostedMesh, from: Vec2, to: Vec2, navmesh: &'a NavMesh) -> Option<Self> {
+        let start = mesh.locate(from, navmesh.mesh.delta())?;
+        let goal = mesh.locate(to, navmesh.mesh.delta())?;
+        // Weighted by the lowest cost so that the estimate never exceeds the remaining cost
+        let cheapest = mesh
+            .polygons
+            .iter()
+            .map(|polygon| navmesh.area_cost(polygon.area))
+            .fold(f32::INFINITY, f32::min);
+        Some(CostedSearch {
+            mesh,
+            navmesh,
+            from,
+            to,
+            goal,
+            cheapest,
+            reached: vec![Reached {
+                polygon: start,
+                point: from,
+                cost: 0.0,
+                previous: None,
+                portal: None,
+                arrived: false,
+            }],
+            best: HashMap::new(),
+            queue: BinaryHeap::from([Candidate {
+                estimate: from.distance(to) * cheapest,
+                reached: 0,
+            }]),
+        })
+    }
+
+    fn cost(&self, polygon: usize) -> f32 {
+        self.navmesh.area_cost(self.mesh.polygons[polygon].area)
+    }
+
+    /// Searches up to `steps` polygons, returning the path once the search is over.
+    fn advance(&mut self, steps: usize) -> Poll<Option<Path>> {
+        let to = self.to;
+        for _ in 0..steps {
+            let Some(Candidate {
+                reached: current, ..
+            }) = self.queue.pop()
+            else {
+                return Poll::Ready(None);
+            };
+            let here = self.reached[current];
+            let Reached {
+                polygon,
+                point,
+                cost: so_far,
+                ..
+            } = here;
+            if here.arrived {
+                return Poll::Ready(Some(self.finish(current)));
+            }
+
+            let walk = self.cost(polygon);
+            let cheapest = self.cheapest;
+            let (reached, queue) = (&mut self.reached, &mut self.queue);
+            let mut push = |next: Reached| {
+                reached.push(next);
+                queue.push(Candidate {
+                    estimate: next.cost + next.point.distance(to) * cheapest,
+                    reached: reached.len() - 1,
+                });
+            };
+            if polygon == self.goal {
+                push(Reached {
+                    point: to,
+                    cost: so_far + walk * point.distance(to),
+                    previous: Some(current),
+                    arrived: true,
+                    ..here
+                });
+            }
+            for (index, portal) in self.mesh.polygons[polygon].portals.iter().enumerate() {
+                // The middle of the portal, and its points closest to the current point and to
+                // the destination
+                let samples = [
+                    (portal.left + portal.right) / 2.0,
+                    closest_on_segment(point, portal.left, portal.right),
+                    closest_on_segment(to, portal.left, portal.right),
+                ];
+                for (kind, sample) in samples.into_iter().enumerate() {
+                    let cost = so_far + walk * point.distance(sample);
+                    let key = (polygon, index, kind);
+                    if self.best.get(&key).is_some_and(|best| *best <= cost) {
+                        continue;
+                    }
+                    self.best.insert(key, cost);
+                    push(Reached {
+                        polygon: portal.to,
+                        point: sample,
+                        cost,
+                        previous: Some(current),
+                        portal: Some(*portal),
+                        arrived: false,
+                    });
+                }
+            }
+        }
+        Poll::Pending
+    }
+
+    /// Builds the path arriving at `arrival`, refining it through the portals crossed.
+    fn finish(&self, arrival: usize) -> Path {
+        let (from, to) = (self.from, self.to);
+        let mut corridor = vec![];
+        let mut node = self.reached[arrival].previous;
+        while let Some(current) = node {
+            corridor.push(self.reached[current]);
+            node = self.reached[current].previous;
+        }
+        corridor.reverse();
+        let portals = corridor
+            .iter()
+            .filter_map(|reached| reached.portal)
+            .collect::<Vec<_>>();
+        let costs = corridor
+            .iter()
+            .map(|reached| self.cost(reached.polygon))
+            .collect::<Vec<_>>();
+        let points = corridor.iter().map(|reached| reached.point).collect();
+        let path = refine(from, to, &portals, &costs, points);
+        let length = iter::once(from)
+            .chain(path.iter().copied())
+            .tuple_windows()
+            .map(|(a, b)| a.distance(b))
+            .sum();
+        Path { length, path }
+    }
+}
+
+/// Adds the portal between two polygons, in both directions.
+fn connect(polygons: &mut [CostedPolygon], a: usize, b: usize, start: Vec2, end: Vec2) {
+    let portal = polygons[a].portal(b, start, end);
+    polygons[a].portals.push(portal);
+    let portal = polygons[b].portal(a, start, end);
+    polygons[b].portals.push(portal);
+}
+
+/// The part shared by two segments on the same line, if it's not a single point.
+fn overlap(a: Vec2, b: Vec2, c: Vec2, d: Vec2) -> Option<(Vec2, Vec2)> {
+    let direction = b - a;
+    let length = direction.length();
+    let off_line = |point: Vec2| direction.perp_dot(point - a).abs() / length;
+    if length == 0.0 || off_line(c) > STRAIGHT_TOLERANCE || off_line(d) > STRAIGHT_TOLERANCE {
+        return None;
+    }
+    let along = |point: Vec2| (point - a).dot(direction) / (length * length);
+    let (from, to) = (
+        along(c).min(along(d)).max(0.0),
+        along(c).max(along(d)).min(1.0),
+    );
+    ((to - from) * length > STRAIGHT_TOLERANCE).then(|| (a + direction * from, a + direction * to))
+}
+
+fn closest_on_segment(point: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let edge = b - a;
+    let along = ((point - a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+    if along.is_finite() {
+        a + edge * along
+    } else {
+        a
+    }
+}
+
+/// Refines a path through a corridor of portals, where `costs[i]` is the cost before crossing
+/// `portals[i]`, and `points[i]` where the search reached the polygon before it.
+fn refine(from: Vec2, to: Vec2, portals: &[Portal], costs: &[f32], points: Vec<Vec2>) -> Vec<Vec2> {
+    // Portals where the cost changes, and the point where the path crosses them
+    let mut changes = (0..portals.len())
+        .filter(|index| costs[*index] != costs[*index + 1])
+        .map(|index| (index, points[index + 1]))
+        .collect::<Vec<_>>();
+    // The path between two changes goes through portals of polygons with the same cost
+    let run = |(start, from): (Option<usize>, Vec2), (end, to): (Option<usize>, Vec2)| {
+        let first = start.map_or(0, |start| start + 1);
+        funnel(from, &portals[first..end.unwrap_or(portals.len())], to)
+    };
+    let length = |points: &[Vec2]| {
+        points
+            .iter()
+            .tuple_windows()
+            .map(|(a, b)| a.distance(*b))
+            .sum::<f32>()
+    };
+
+    for _ in 0..16 {
+        let mut improved = false;
+        for change in 0..changes.len() {
+            let (index, current) = changes[change];
+            let before = match change {
+                0 => (None, from),
+                _ => (Some(changes[change - 1].0), changes[change - 1].1),
+            };
+            let after = changes
+                .get(change + 1)
+                .map_or((None, to), |(index, point)| (Some(*index), *point));
+            let cost = |point: Vec2| {
+                let walk_before = iter::once(before.1).chain(run(before, (Some(index), point)));
+                let walk_after = iter::once(point).chain(run((Some(index), point), after));
+                costs[index] * length(&walk_before.collect::<Vec<_>>())
+                    + costs[index + 1] * length(&walk_after.collect::<Vec<_>>())
+            };
+            let Portal { left, right, .. } = portals[index];
+            let (mut low, mut high) = (0.0, 1.0);
+            for _ in 0..32 {
+                let third = (high - low) / 3.0;
+                if cost(left.lerp(right, low + third)) < cost(left.lerp(right, high - third)) {
+                    high -= third;
+                } else {
+                    low += third;
+                }
+            }
+            let candidate = left.lerp(right, (low + high) / 2.0);
+            if cost(candidate) < cost(current) - STRAIGHT_TOLERANCE * STRAIGHT_TOLERANCE {
+                changes[change].1 = candidate;
+                improved = true;
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let mut path: Vec<Vec2> = vec![];
+    let mut previous = (None, from);
+    for (index, point) in changes
+        .into_iter()
+        .map(|(index, point)| (Some(index), point))
+        .chain([(None, to)])
+    {
+        path.extend(run(previous, (index, point)));
+        previous = (index, point);
+    }
+    straighten(from, path)
+}
+
+/// The shortest path from `from` to `to` through a list of portals, with the funnel algorithm.
+fn funnel(from: Vec2, portals: &[Portal], to: Vec2) -> Vec<Vec2> {
+    let gates = portals
+        .iter()
+        .map(|portal| (portal.left, portal.right))
+        .chain([(to, to)])
+        .collect::<Vec<_>>();
+    let mut path = vec![];
+    let (mut apex, mut left, mut right) = (from, from, from);
+    let (mut left_index, mut right_index) = (0, 0);
+    let mut index = 0;
+    while index < gates.len() {
+        let (next_left, next_right) = gates[index];
+        if (right - apex).perp_dot(next_right - apex) >= 0.0 {
+            if apex == right || (left - apex).perp_dot(next_right - apex) < 0.0 {
+                right = next_right;
+                right_index = index;
+            } else {
+                // The right side crosses the left one, the path turns around it
+                path.push(left);
+                apex = left;
+                right = left;
+                right_index = left_index;
+                index = left_index + 1;
+                continue;
+            }
+        }
+        if (left - apex).perp_dot(next_left - apex) <= 0.0 {
+            if apex == left || (right - apex).perp_dot(next_left - apex) > 0.0 {
+                left = next_left;
+                left_index = index;
+            } else {
+                path.push(right);
+                apex = right;
+                left = right;
+                left_index = right_index;
+                index = right_index + 1;
+                continue;
+            }
+        }
+        index += 1;
+    }
+    path.push(to);
+    path
+}
+
+/// Removes duplicated points, and points on a straight line between their neighbours.
+fn straighten(from: Vec2, points: Vec<Vec2>) -> Vec<Vec2> {
+    let mut path: Vec<Vec2> = vec![];
+    for point in points {
+        if *path.last().unwrap_or(&from) == point {
+            continue;
+        }
+        while let Some(last) = path.last().copied() {
+            let before = path.iter().nth_back(1).copied().unwrap_or(from);
+            if last.distance(closest_on_segment(last, before, point)) > STRAIGHT_TOLERANCE {
+                break;
+            }
+            path.pop();
+        }
+        path.push(point);
+    }
+    path
+}
+
+/// Area types a path can't go through, so that one [`NavMesh`] can be shared by agents that can
+/// walk different parts of a level.
+///
+/// ```
+/// # use vleue_navigator::areas::NavQueryFilter;
+/// const WATER: u8 = 1;
+/// const DEEP_WATER: u8 = 2;
+/// let infantry = NavQueryFilter::default().exclude(DEEP_WATER);
+/// let vehicles = NavQueryFilter::default().exclude(WATER).exclude(DEEP_WATER);
+/// # assert!(vehicles.is_excluded(WATER));
+/// # assert!(!infantry.is_excluded(WATER));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct NavQueryFilter {
+    /// Sorted area types that can't be walked
+    pub(crate) excluded: Vec<u8>,
+}
+
+impl NavQueryFilter {
+    /// Marks an area type as impassable. Excluding [`DEFAULT_AREA`] keeps only the parts of the
+    /// [`NavMesh`] tagged with another area type.
+    pub fn exclude(mut self, area: u8) -> Self {
+        if let Err(index) = self.excluded.binary_search(&area) {
+            self.excluded.insert(index, area);
+        }
+        self
+    }
+
+    /// Checks if an area type is impassable.
+    pub fn is_excluded(&self, area: u8) -> bool {
+        self.excluded.binary_search(&area).is_ok()
+    }
+}
+
+/// Reads the area type of each vertex of a Bevy [`Mesh`], if it has the [`ATTRIBUTE_NAV_AREA`].
+///
+/// Values must be whole numbers from `0` to `255`.
+pub(crate) fn get_areas(mesh: &Mesh) -> Result<Option<Vec<u8>>, NavMeshBuildError> {
+    let unsupported = NavMeshBuildError::UnsupportedAttributeFormat(ATTRIBUTE_NAV_AREA.name);
+    match mesh.attribute(ATTRIBUTE_NAV_AREA) {
+        None => Ok(None),
+        Some(VertexAttributeValues::Float32(values)) => values
+            .iter()
+            .map(|area| {
+                (area.fract() == 0.0 && (0.0..=u8::MAX as f32).contains(area))
+                    .then_some(*area as u8)
+            })
+            .collect::<Option<_>>()
+            .map(Some)
+            .ok_or(unsupported),
+        Some(_) => Err(unsupported),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::PrimitiveTopology;
+    use bevy::render::{mesh::Indices, render_asset::RenderAssetUsages};
+
+    use bevy::tasks::block_on;
+
+    use super::*;
+    use crate::test_fixtures::{square, square_navmesh, square_with_obstacle};
+
+    const SWAMP: u8 = 1;
+
+    fn with_swamp(cost: f32) -> NavMesh {
+        let mut navmesh = square_navmesh();
+        navmesh.set_area(
+            SWAMP,
+            &[
+                Vec2::new(4., -1.),
+                Vec2::new(6., -1.),
+                Vec2::new(6., 8.),
+                Vec2::new(4., 8.),
+            ],
+        );
+        navmesh.set_area_cost(SWAMP, cost).unwrap();
+        navmesh
+    }
+
+    #[test]
+    fn costly_area_is_avoided() {
+        let navmesh = with_swamp(10.);
+        assert_eq!(navmesh.area_at(Vec2::new(5., 5.)), SWAMP);
+        assert_eq!(navmesh.area_at(Vec2::new(5., 9.)), DEFAULT_AREA);
+
+        let from = Vec2::new(1., 1.);
+        let path = navmesh.path(from, Vec2::new(9., 1.)).unwrap();
+        assert!(path.path.iter().any(|point| point.y >= 8.));
+        assert!(navmesh.path_cost(from, &path) < 26.);
+    }
+
+    #[test]
+    fn path_cost_is_optimal() {
+        let (from, to) = (Vec2::new(1., 1.), Vec2::new(9., 1.));
+        // Crossing the swamp costs 6 plus twice its cost, going around it costs 2 * sqrt(58) + 2
+        let detour = 2. * 58f32.sqrt() + 2.;
+        for (cost, cheapest) in [(2., 10.), (5.5, 17.), (5.7, detour), (10., detour)] {
+            let navmesh = with_swamp(cost);
+            let path = navmesh.path(from, to).unwrap();
+            let found = navmesh.path_cost(from, &path);
+            assert!((found - cheapest).abs() < 0.01, "{cost}: {found} {path:?}");
+        }
+    }
+
+    #[test]
+    fn costed_search_matches_polyanya_with_equal_costs() {
+        // A pillar, with an area type around it that doesn't change the cost
+        let mut navmesh = square_with_obstacle(Vec2::new(4., 4.), Vec2::new(6., 6.));
+        navmesh.set_area(SWAMP, &square(Vec2::new(3., 2.), Vec2::new(7., 8.)));
+        let costed = navmesh.costed_mesh(&[]).unwrap();
+
+        for (from, to) in [
+            (Vec2::new(1., 5.), Vec2::new(9., 5.)),
+            (Vec2::new(5., 1.), Vec2::new(5., 9.)),
+            (Vec2::new(1., 1.), Vec2::new(9., 9.)),
+            (Vec2::new(3.5, 5.), Vec2::new(8., 6.5)),
+        ] {
+            let expected = navmesh.mesh.path(from, to).unwrap();
+            let path = costed.path(from, to, &navmesh).unwrap();
+            assert!(
+                (path.length - expected.length).abs() < 0.01,
+                "{from} {to}: {path:?} {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn cheap_enough_area_is_crossed() {
+        let navmesh = with_swamp(2.);
+
+        let from = Vec2::new(1., 1.);
+        let path = navmesh.path(from, Vec2::new(9., 1.)).unwrap();
+        assert_eq!(path.path, vec![Vec2::new(9., 1.)]);
+        assert_eq!(navmesh.path_cost(from, &path), 10.);
+    }
+
+    #[test]
+    fn cheap_area_is_preferred() {
+        const ROAD: u8 = 2;
+        let mut navmesh = NavMesh::from_polyanya_mesh(
+            polyanya::Trimesh {
+                vertices: vec![
+                    Vec2::new(0., 0.),
+                    Vec2::new(20., 0.),
+                    Vec2::new(20., 10.),
+                    Vec2::new(0., 10.),
+                ],
+                triangles: vec![[0, 1, 2], [0, 2, 3]],
+            }
+            .into(),
+        );
+        navmesh.set_area(
+            ROAD,
+            &[
+                Vec2::new(-1., 4.),
+                Vec2::new(21., 4.),
+                Vec2::new(21., 6.),
+                Vec2::new(-1., 6.),
+            ],
+        );
+        let from = Vec2::new(1., 3.);
+        let to = Vec2::new(19., 3.);
+        assert_eq!(navmesh.path(from, to).unwrap().path, vec![to]);
+
+        // Joining the road where the cost of the grass and of the road balance out
+        navmesh.set_area_cost(ROAD, 0.5).unwrap();
+        let path = navmesh.path(from, to).unwrap();
+        assert_eq!(path.path.len(), 3);
+        let join = 1. + 1. / 3f32.sqrt();
+        assert!(path.path[0].distance(Vec2::new(join, 4.)) < 0.01);
+        assert!(path.path[1].distance(Vec2::new(20. - join, 4.)) < 0.01);
+        assert_eq!(path.path[2], to);
+    }
+
+    #[test]
+    fn area_cost_must_be_positive() {
+        let mut navmesh = with_swamp(2.);
+        for cost in [0., -1., f32::NAN, f32::INFINITY] {
+            assert!(matches!(
+                navmesh.set_area_cost(SWAMP, cost),
+                Err(NavMeshBuildError::InvalidSetting("cost"))
+            ));
+        }
+        assert_eq!(navmesh.area_cost(SWAMP), 2.);
+    }
+
+    #[test]
+    fn async_path_avoids_costly_area() {
+        let navmesh = with_swamp(10.);
+        let (from, to) = (Vec2::new(1., 1.), Vec2::new(9., 1.));
+        let path = block_on(navmesh.get_path(from, to)).unwrap();
+        assert_eq!(path.path, navmesh.path(from, to).unwrap().path);
+    }
+
+    #[test]
+    fn filter_excludes_area_types() {
+        let navmesh = with_swamp(1.);
+        let from = Vec2::new(1., 1.);
+        let to = Vec2::new(9., 1.);
+
+        let path = navmesh.path(from, to).unwrap();
+        assert_eq!(path.path, vec![to]);
+
+        let no_swamp = NavQueryFilter::default().exclude(SWAMP);
+        let path = navmesh.filtered_path(from, to, &no_swamp).unwrap();
+        assert!(path.path.iter().any(|point| point.y >= 8.));
+        assert!(navmesh
+            .filtered_path(from, Vec2::new(5., 1.), &no_swamp)
+            .is_none());
+
+        let swamp_only = NavQueryFilter::default().exclude(DEFAULT_AREA);
+        assert!(navmesh
+            .filtered_path(Vec2::new(4.5, 1.), Vec2::new(5.5, 7.), &swamp_only)
+            .is_some());
+        assert!(navmesh.filtered_path(from, to, &swamp_only).is_none());
+    }
+
+    #[test]
+    fn areas_are_read_from_bevy_mesh() {
+        // Two quads side by side, the second one being a swamp. The first vertex of both swamp
+        // triangles is on the edge between the quads, and tagged like the first quad.
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0., 0., 0.],
+                [1., 0., 0.],
+                [1., 1., 0.],
+                [0., 1., 0.],
+                [1., 0., 0.],
+                [2., 0., 0.],
+                [2., 1., 0.],
+                [1., 1., 0.],
+            ],
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; 8]);
+        mesh.insert_attribute(ATTRIBUTE_NAV_AREA, vec![0., 0., 0., 0., 0., 1., 1., 1.]);
+        mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3, 4, 5, 6, 4, 6, 7]));
+
+        for invalid in [-1., 0.5, 256., f32::NAN] {
+            let mut mesh = mesh.clone();
+            mesh.insert_attribute(
+                ATTRIBUTE_NAV_AREA,
+                vec![0., 0., 0., 0., 0., invalid, 1., 1.],
+            );
+            assert!(matches!(
+                get_areas(&mesh),
+                Err(NavMeshBuildError::UnsupportedAttributeFormat(_))
+            ));
+        }
+
+        let navmesh = NavMesh::from_bevy_mesh(&mesh);
+        assert_eq!(navmesh.area_at(Vec2::new(0.5, 0.5)), DEFAULT_AREA);
+        assert_eq!(navmesh.area_at(Vec2::new(1.5, 0.5)), SWAMP);
+        assert!(navmesh
+            .path(Vec2::new(0.5, 0.5), Vec2::new(1.5, 0.5))
+            .is_some());
+    }
+}
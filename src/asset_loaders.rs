@@ -1,27 +1,65 @@
 //! Asset loaders that can load a [`NavMesh`] from a file
 
-use std::{error::Error, fmt::Display, sync::Arc};
+use std::{error::Error, fmt::Display};
 
+#[cfg(feature = "gltf")]
+use bevy::math::Mat4;
 use bevy::{
     asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
-    prelude::{Transform, Vec3},
     utils::BoxedFuture,
 };
 use polyanya::PolyanyaFile;
+#[cfg(feature = "gltf")]
+use serde::{Deserialize, Serialize};
 
-use crate::NavMesh;
+#[cfg(feature = "png")]
+use crate::image_mask::ImageMaskSettings;
+#[cfg(feature = "svg")]
+use crate::svg::SvgSettings;
+#[cfg(feature = "tiled")]
+use crate::tiled::TiledSettings;
+
+use crate::{NavMesh, NavMeshBuildError};
 
 /// Error that can happen while reading a `NavMesh` from a file
 #[derive(Debug)]
 pub enum NavMeshLoaderError {
     /// Error when reading file
     Io(std::io::Error),
+    /// Error when reading a glTF file
+    #[cfg(feature = "gltf")]
+    Gltf(bevy::gltf::GltfError),
+    /// Error when reading an image
+    #[cfg(feature = "png")]
+    Image(image::ImageError),
+    /// Error when reading a Tiled map
+    #[cfg(feature = "tiled")]
+    Tiled(crate::tiled::TiledMapError),
+    /// Error when reading an SVG file
+    #[cfg(feature = "svg")]
+    Svg(crate::svg::SvgError),
+    /// The mesh, node or primitive to use was not found in the file
+    NotFound(String),
+    /// Error when building the `NavMesh` from the loaded mesh
+    Build(NavMeshBuildError),
 }
 
 impl Display for NavMeshLoaderError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             NavMeshLoaderError::Io(io_error) => write!(f, "IO error: {}", io_error),
+            #[cfg(feature = "gltf")]
+            NavMeshLoaderError::Gltf(gltf_error) => write!(f, "glTF error: {}", gltf_error),
+            #[cfg(feature = "png")]
+            NavMeshLoaderError::Image(image_error) => write!(f, "image error: {}", image_error),
+            #[cfg(feature = "tiled")]
+            NavMeshLoaderError::Tiled(tiled_error) => write!(f, "Tiled error: {}", tiled_error),
+            #[cfg(feature = "svg")]
+            NavMeshLoaderError::Svg(svg_error) => write!(f, "SVG error: {}", svg_error),
+            NavMeshLoaderError::NotFound(name) => write!(f, "{} not found", name),
+            NavMeshLoaderError::Build(build_error) => {
+                write!(f, "NavMesh build error: {}", build_error)
+            }
         }
     }
 }
@@ -30,6 +68,16 @@ impl Error for NavMeshLoaderError {
     fn source(&self) -> Option<&(dyn Error + 'static)> {
         match self {
             NavMeshLoaderError::Io(io_error) => Some(io_error),
+            #[cfg(feature = "gltf")]
+            NavMeshLoaderError::Gltf(gltf_error) => Some(gltf_error),
+            #[cfg(feature = "png")]
+            NavMeshLoaderError::Image(image_error) => Some(image_error),
+            #[cfg(feature = "tiled")]
+            NavMeshLoaderError::Tiled(tiled_error) => Some(tiled_error),
+            #[cfg(feature = "svg")]
+            NavMeshLoaderError::Svg(svg_error) => Some(svg_error),
+            NavMeshLoaderError::NotFound(_) => None,
+            NavMeshLoaderError::Build(build_error) => Some(build_error),
         }
     }
 }
@@ -56,10 +104,8 @@ impl AssetLoader for NavMeshPolyanyaLoader {
                 .read_to_end(&mut bytes)
                 .await
                 .map_err(NavMeshLoaderError::Io)?;
-            let navmesh = NavMesh {
-                mesh: Arc::new(PolyanyaFile::from_bytes(bytes.as_slice()).into()),
-                transform: Transform::from_scale(Vec3::splat(1.)),
-            };
+            let navmesh =
+                NavMesh::from_polyanya_mesh(PolyanyaFile::from_bytes(bytes.as_slice()).into());
             Ok(navmesh)
         })
     }
@@ -68,3 +114,545 @@ impl AssetLoader for NavMeshPolyanyaLoader {
         &["polyanya.mesh"]
     }
 }
+
+/// Which part of a glTF file is used to build a [`NavMesh`] with [`NavMeshGltfLoader`].
+#[cfg(feature = "gltf")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GltfNavMeshSource {
+    /// A mesh, by name
+    Mesh(String),
+    /// A node, by name. The transform of the node in the default scene, including the
+    /// transforms of its parents, is applied to its mesh. Nodes that are in no scene only use
+    /// their local transform.
+    Node(String),
+}
+
+/// Settings for [`NavMeshGltfLoader`].
+#[cfg(feature = "gltf")]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NavMeshGltfLoaderSettings {
+    /// The mesh or node to use. Default is the mesh named `navmesh`.
+    pub source: GltfNavMeshSource,
+    /// Index of the primitive of the mesh to use. Default is the first one.
+    pub primitive: usize,
+}
+
+#[cfg(feature = "gltf")]
+impl Default for NavMeshGltfLoaderSettings {
+    fn default() -> Self {
+        Self {
+            source: GltfNavMeshSource::Mesh("navmesh".to_string()),
+            primitive: 0,
+        }
+    }
+}
+
+/// Asset loader for a mesh in a glTF file with a `.navmesh.glb` or `.navmesh.gltf` extension.
+///
+/// The mesh used is chosen with [`NavMeshGltfLoaderSettings`], then converted with
+/// [`NavMesh::try_from_bevy_mesh`]. Area types are read from the `_NAV_AREA` vertex attribute,
+/// see [`ATTRIBUTE_NAV_AREA`](crate::areas::ATTRIBUTE_NAV_AREA).
+///
+/// ```rust,no_run
+/// # use bevy::prelude::*;
+/// # use vleue_navigator::{NavMesh, asset_loaders::{GltfNavMeshSource, NavMeshGltfLoaderSettings}};
+/// fn load(asset_server: Res<AssetServer>) {
+///     let navmesh: Handle<NavMesh> = asset_server.load_with_settings(
+///         "level.navmesh.glb",
+///         |settings: &mut NavMeshGltfLoaderSettings| {
+///             settings.source = GltfNavMeshSource::Node("walkable".to_string());
+///         },
+///     );
+/// }
+/// ```
+#[cfg(feature = "gltf")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct NavMeshGltfLoader;
+
+#[cfg(feature = "gltf")]
+impl AssetLoader for NavMeshGltfLoader {
+    type Asset = NavMesh;
+    type Settings = NavMeshGltfLoaderSettings;
+    type Error = NavMeshLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        settings: &'a Self::Settings,
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            use bevy::{
+                gltf::GltfError,
+                render::{
+                    mesh::{Indices, Mesh},
+                    render_asset::RenderAssetUsages,
+                    render_resource::PrimitiveTopology,
+                },
+                transform::components::GlobalTransform,
+            };
+            use gltf::{mesh::Mode, Semantic};
+
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(NavMeshLoaderError::Io)?;
+            // Only the document is parsed, images, materials and scenes are not loaded
+            let gltf = gltf::Gltf::from_slice(&bytes)
+                .map_err(|error| NavMeshLoaderError::Gltf(GltfError::Gltf(error)))?;
+
+            let (gltf_mesh, transform) = match &settings.source {
+                GltfNavMeshSource::Mesh(name) => (
+                    gltf.meshes()
+                        .find(|mesh| mesh.name() == Some(name))
+                        .ok_or_else(|| NavMeshLoaderError::NotFound(format!("mesh {name}")))?,
+                    GlobalTransform::IDENTITY,
+                ),
+                GltfNavMeshSource::Node(name) => {
+                    let node = gltf
+                        .nodes()
+                        .find(|node| node.name() == Some(name))
+                        .ok_or_else(|| NavMeshLoaderError::NotFound(format!("node {name}")))?;
+                    let transform = node_transform(&gltf, node.index()).unwrap_or_else(|| {
+                        Mat4::from_cols_array_2d(&node.transform().matrix()).into()
+                    });
+                    (
+                        node.mesh().ok_or_else(|| {
+                            NavMeshLoaderError::NotFound(format!("mesh of node {name}"))
+                        })?,
+                        transform,
+                    )
+                }
+            };
+            let primitive = gltf_mesh
+                .primitives()
+                .nth(settings.primitive)
+                .ok_or_else(|| {
+                    NavMeshLoaderError::NotFound(format!("primitive {}", settings.primitive))
+                })?;
+            if primitive.mode() != Mode::Triangles {
+                return Err(NavMeshLoaderError::Gltf(GltfError::UnsupportedPrimitive {
+                    mode: primitive.mode(),
+                }));
+            }
+
+            // Buffers used by the primitive, by index in the file
+            let area = primitive.get(&Semantic::Extras("NAV_AREA".to_string()));
+            let mut buffers = vec![None; gltf.buffers().len()];
+            for accessor in primitive
+                .attributes()
+                .map(|(_, accessor)| accessor)
+                .chain(primitive.indices())
+            {
+                let views = accessor.view().into_iter().chain(
+                    accessor
+                        .sparse()
+                        .into_iter()
+                        .flat_map(|sparse| [sparse.indices().view(), sparse.values().view()]),
+                );
+                for view in views {
+                    let buffer = view.buffer();
+                    let index = buffer.index();
+                    if buffers[index].is_none() {
+                        buffers[index] = Some(load_buffer(&gltf, buffer, load_context).await?);
+                    }
+                }
+            }
+            let reader = primitive.reader(|buffer| buffers[buffer.index()].as_deref());
+
+            let positions = reader
+                .read_positions()
+                .ok_or(NavMeshLoaderError::Build(
+                    NavMeshBuildError::MissingAttribute(Mesh::ATTRIBUTE_POSITION.name),
+                ))?
+                .collect::<Vec<_>>();
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().collect(),
+                None => (0..positions.len() as u32).collect(),
+            };
+            let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+            mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+            mesh.insert_indices(Indices::U32(indices));
+            if let Some(area) = area {
+                let areas = read_areas(area, &buffers).ok_or(NavMeshLoaderError::Build(
+                    NavMeshBuildError::UnsupportedAttributeFormat(
+                        crate::areas::ATTRIBUTE_NAV_AREA.name,
+                    ),
+                ))?;
+                mesh.insert_attribute(crate::areas::ATTRIBUTE_NAV_AREA, areas);
+            }
+
+            NavMesh::try_from_bevy_meshes(&[(&mesh, transform)]).map_err(NavMeshLoaderError::Build)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["navmesh.glb", "navmesh.gltf"]
+    }
+}
+
+/// Reads the area types of the vertices of a glTF primitive, as floats like
+/// [`ATTRIBUTE_NAV_AREA`](crate::areas::ATTRIBUTE_NAV_AREA). Unsigned integers are converted, other
+/// formats are not supported.
+#[cfg(feature = "gltf")]
+fn read_areas<'a>(
+    accessor: gltf::Accessor<'a>,
+    buffers: &'a [Option<Vec<u8>>],
+) -> Option<Vec<f32>> {
+    use gltf::accessor::{DataType, Dimensions, Iter};
+
+    if accessor.dimensions() != Dimensions::Scalar || accessor.normalized() {
+        return None;
+    }
+    let get_buffer = |buffer: gltf::Buffer| buffers[buffer.index()].as_deref();
+    Some(match accessor.data_type() {
+        DataType::F32 => Iter::<f32>::new(accessor, get_buffer)?.collect(),
+        DataType::U8 => Iter::<u8>::new(accessor, get_buffer)?
+            .map(f32::from)
+            .collect(),
+        DataType::U16 => Iter::<u16>::new(accessor, get_buffer)?
+            .map(f32::from)
+            .collect(),
+        DataType::U32 => Iter::<u32>::new(accessor, get_buffer)?
+            .map(|area| area as f32)
+            .collect(),
+        _ => return None,
+    })
+}
+
+/// Reads the data of a buffer of a glTF file, from its binary chunk, a data URI or a file next
+/// to it.
+#[cfg(feature = "gltf")]
+async fn load_buffer(
+    gltf: &gltf::Gltf,
+    buffer: gltf::Buffer<'_>,
+    load_context: &mut LoadContext<'_>,
+) -> Result<Vec<u8>, NavMeshLoaderError> {
+    use base64::Engine;
+    use bevy::gltf::GltfError;
+
+    const BASE64_PREFIXES: &[&str] = &[
+        "data:application/octet-stream;base64,",
+        "data:application/gltf-buffer;base64,",
+    ];
+
+    match buffer.source() {
+        gltf::buffer::Source::Bin => gltf
+            .blob
+            .clone()
+            .ok_or(NavMeshLoaderError::Gltf(GltfError::MissingBlob)),
+        gltf::buffer::Source::Uri(uri) if uri.starts_with("data:") => {
+            let Some(data) = BASE64_PREFIXES
+                .iter()
+                .find_map(|prefix| uri.strip_prefix(prefix))
+            else {
+                return Err(NavMeshLoaderError::Gltf(GltfError::BufferFormatUnsupported));
+            };
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map_err(|error| NavMeshLoaderError::Gltf(GltfError::Base64Decode(error)))
+        }
+        gltf::buffer::Source::Uri(uri) => {
+            let uri = percent_encoding::percent_decode_str(uri).decode_utf8_lossy();
+            let path = load_context
+                .path()
+                .parent()
+                .unwrap_or(std::path::Path::new(""))
+                .join(uri.as_ref());
+            load_context
+                .read_asset_bytes(path)
+                .await
+                .map_err(|error| NavMeshLoaderError::Gltf(GltfError::ReadAssetBytesError(error)))
+        }
+    }
+}
+
+/// Transform of the node with index `node` in the default scene of a glTF file, or in the first
+/// scene it is in, combined with the transforms of its parents.
+#[cfg(feature = "gltf")]
+fn node_transform(
+    gltf: &gltf::Gltf,
+    node: usize,
+) -> Option<bevy::transform::components::GlobalTransform> {
+    fn find(parent: Mat4, current: gltf::Node, node: usize) -> Option<Mat4> {
+        let transform = parent * Mat4::from_cols_array_2d(&current.transform().matrix());
+        if current.index() == node {
+            return Some(transform);
+        }
+        current
+            .children()
+            .find_map(|child| find(transform, child, node))
+    }
+
+    gltf.default_scene()
+        .into_iter()
+        .chain(gltf.scenes())
+        .flat_map(|scene| scene.nodes())
+        .find_map(|root| find(Mat4::IDENTITY, root, node))
+        .map(Into::into)
+}
+
+/// Asset loader for an image mask with a `.navmask.png` extension.
+///
+/// The image is read with [`NavMesh::from_image_mask`], using the [`ImageMaskSettings`] of the
+/// loader.
+///
+/// ```rust,no_run
+/// # use bevy::prelude::*;
+/// # use vleue_navigator::{NavMesh, image_mask::ImageMaskSettings};
+/// fn load(asset_server: Res<AssetServer>) {
+///     let navmesh: Handle<NavMesh> = asset_server.load_with_settings(
+///         "level.navmask.png",
+///         |settings: &mut ImageMaskSettings| {
+///             settings.scale = 0.1;
+///         },
+///     );
+/// }
+/// ```
+#[cfg(feature = "png")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct NavMeshImageMaskLoader;
+
+#[cfg(feature = "png")]
+impl AssetLoader for NavMeshImageMaskLoader {
+    type Asset = NavMesh;
+    type Settings = ImageMaskSettings;
+    type Error = NavMeshLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            use bevy::render::{render_asset::RenderAssetUsages, texture::Image};
+
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(NavMeshLoaderError::Io)?;
+            let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+                .map_err(NavMeshLoaderError::Image)?;
+            let image = Image::from_dynamic(image, true, RenderAssetUsages::default());
+
+            NavMesh::from_image_mask(&image, settings).map_err(NavMeshLoaderError::Build)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["navmask.png"]
+    }
+}
+
+/// Asset loader for an object layer of a Tiled map with a `.navmesh.tmx` extension.
+///
+/// The map is read with [`NavMesh::from_tiled_map`], using the [`TiledSettings`] of the loader
+/// to choose the layer and the classes of objects.
+///
+/// ```rust,no_run
+/// # use bevy::prelude::*;
+/// # use vleue_navigator::{NavMesh, tiled::TiledSettings};
+/// fn load(asset_server: Res<AssetServer>) {
+///     let navmesh: Handle<NavMesh> = asset_server.load_with_settings(
+///         "level.navmesh.tmx",
+///         |settings: &mut TiledSettings| {
+///             settings.layer = "collisions".to_string();
+///         },
+///     );
+/// }
+/// ```
+#[cfg(feature = "tiled")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct NavMeshTiledLoader;
+
+#[cfg(feature = "tiled")]
+impl AssetLoader for NavMeshTiledLoader {
+    type Asset = NavMesh;
+    type Settings = TiledSettings;
+    type Error = NavMeshLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut map = String::new();
+            reader
+                .read_to_string(&mut map)
+                .await
+                .map_err(NavMeshLoaderError::Io)?;
+            NavMesh::from_tiled_map(&map, settings).map_err(NavMeshLoaderError::Tiled)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["navmesh.tmx"]
+    }
+}
+
+/// Asset loader for the shapes of an SVG file with a `.navmesh.svg` extension.
+///
+/// The file is read with [`NavMesh::from_svg`], using the [`SvgSettings`] of the loader.
+///
+/// ```rust,no_run
+/// # use bevy::prelude::*;
+/// # use vleue_navigator::{NavMesh, svg::SvgSettings};
+/// fn load(asset_server: Res<AssetServer>) {
+///     let navmesh: Handle<NavMesh> = asset_server.load_with_settings(
+///         "level.navmesh.svg",
+///         |settings: &mut SvgSettings| {
+///             settings.scale = 0.01;
+///         },
+///     );
+/// }
+/// ```
+#[cfg(feature = "svg")]
+#[derive(Default, Debug, Clone, Copy)]
+pub struct NavMeshSvgLoader;
+
+#[cfg(feature = "svg")]
+impl AssetLoader for NavMeshSvgLoader {
+    type Asset = NavMesh;
+    type Settings = SvgSettings;
+    type Error = NavMeshLoaderError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        settings: &'a Self::Settings,
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Self::Asset, Self::Error>> {
+        Box::pin(async move {
+            let mut svg = String::new();
+            reader
+                .read_to_string(&mut svg)
+                .await
+                .map_err(NavMeshLoaderError::Io)?;
+            NavMesh::from_svg(&svg, settings).map_err(NavMeshLoaderError::Svg)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["navmesh.svg"]
+    }
+}
+
+#[cfg(all(test, feature = "gltf"))]
+mod tests {
+    use bevy::{
+        asset::{AssetPlugin, LoadState},
+        prelude::*,
+    };
+
+    use super::*;
+    use crate::VleueNavigatorPlugin;
+
+    fn load_gltf(settings: NavMeshGltfLoaderSettings) -> NavMesh {
+        let mut app = App::new();
+        // No other asset types are needed, only the mesh is read from the file
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), VleueNavigatorPlugin));
+        let handle: Handle<NavMesh> = app.world.resource::<AssetServer>().load_with_settings(
+            "meshes/level.navmesh.glb",
+            move |loader_settings: &mut NavMeshGltfLoaderSettings| {
+                *loader_settings = settings.clone();
+            },
+        );
+        for _ in 0..1000 {
+            app.update();
+            match app.world.resource::<AssetServer>().load_state(&handle) {
+                LoadState::Loaded => break,
+                LoadState::Failed => panic!("failed to load the navmesh"),
+                _ => std::thread::sleep(std::time::Duration::from_millis(1)),
+            }
+        }
+        app.world
+            .resource::<Assets<NavMesh>>()
+            .get(&handle)
+            .unwrap()
+            .clone()
+    }
+
+    #[test]
+    fn navmesh_is_loaded_from_gltf() {
+        let navmesh = load_gltf(NavMeshGltfLoaderSettings::default());
+        assert!(navmesh.transformed_is_in_mesh(Vec3::new(0.0, 0.0, 20.0)));
+        assert!(!navmesh.transformed_is_in_mesh(Vec3::new(0.0, 0.0, 30.0)));
+        let surface = navmesh
+            .transformed_closest_point(Vec3::new(0.0, 5.0, 20.0))
+            .unwrap();
+        assert!(surface.y.abs() < 1e-4, "{surface}");
+
+        // The node moves the same mesh up
+        let navmesh = load_gltf(NavMeshGltfLoaderSettings {
+            source: GltfNavMeshSource::Node("navmesh-applied".to_string()),
+            primitive: 0,
+        });
+        let surface = navmesh
+            .transformed_closest_point(Vec3::new(0.0, 5.0, 20.0))
+            .unwrap();
+        assert!((surface.y - 0.1).abs() < 1e-4, "{surface}");
+    }
+
+    #[test]
+    fn node_transform_includes_parents() {
+        let gltf = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "scene": 0,
+                "scenes": [{ "nodes": [0] }],
+                "nodes": [
+                    { "translation": [1.0, 2.0, 3.0], "children": [1] },
+                    { "name": "navmesh", "rotation": [0.0, 0.70710677, 0.0, 0.70710677] },
+                    { "name": "orphan", "translation": [5.0, 0.0, 0.0] }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let transform = node_transform(&gltf, 1).unwrap();
+        let point = transform.transform_point(Vec3::X);
+        assert!(point.distance(Vec3::new(1.0, 2.0, 2.0)) < 1e-5, "{point}");
+        assert!(node_transform(&gltf, 2).is_none());
+    }
+
+    #[test]
+    fn areas_are_read_from_floats_and_unsigned_integers() {
+        let gltf = gltf::Gltf::from_slice(
+            br#"{
+                "asset": { "version": "2.0" },
+                "buffers": [{ "byteLength": 12 }],
+                "bufferViews": [
+                    { "buffer": 0, "byteLength": 4 },
+                    { "buffer": 0, "byteOffset": 4, "byteLength": 8 }
+                ],
+                "accessors": [
+                    { "bufferView": 0, "componentType": 5121, "count": 4, "type": "SCALAR" },
+                    { "bufferView": 1, "componentType": 5126, "count": 2, "type": "SCALAR" },
+                    { "bufferView": 1, "componentType": 5122, "count": 4, "type": "SCALAR" },
+                    { "bufferView": 1, "componentType": 5126, "count": 1, "type": "VEC2" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let buffer = [1u8, 2, 3, 255]
+            .into_iter()
+            .chain([3.0f32, 4.0].into_iter().flat_map(f32::to_le_bytes))
+            .collect();
+        let buffers = [Some(buffer)];
+        let accessor = |index| gltf.accessors().nth(index).unwrap();
+
+        assert_eq!(
+            read_areas(accessor(0), &buffers),
+            Some(vec![1.0, 2.0, 3.0, 255.0])
+        );
+        assert_eq!(read_areas(accessor(1), &buffers), Some(vec![3.0, 4.0]));
+        // Signed integers and vectors are not area types
+        assert_eq!(read_areas(accessor(2), &buffers), None);
+        assert_eq!(read_areas(accessor(3), &buffers), None);
+    }
+}
@@ -0,0 +1,874 @@
+//! Bake a [`NavMesh`] from level geometry by voxelizing it, the way Recast does
+//!
+//! The triangles of the level are rasterized into columns of solid spans. Spans with a surface
+//! flat enough, enough free height above them and no ledge next to them are walkable. The
+//! walkable area is then eroded by the radius of the agents, and triangulated.
+
+use std::{cmp::Reverse, collections::BinaryHeap, f32::consts::FRAC_PI_4, sync::Arc};
+
+use bevy::{math::IVec2, prelude::*, render::render_resource::PrimitiveTopology, utils::HashMap};
+use itertools::Itertools;
+
+use crate::{
+    builder::{to_polygon, triangulate, union_all},
+    get_vectors,
+    heights::HeightMap,
+    NavMesh, NavMeshBuildError, WELDING_DISTANCE,
+};
+
+/// Spans with their tops closer than this number of voxels merge their walkable flags.
+const MERGE_THRESHOLD: i32 = 1;
+
+/// Neighbouring columns, in an order where each direction is followed by the next one clockwise.
+const DIRECTIONS: [IVec2; 4] = [IVec2::NEG_X, IVec2::Y, IVec2::X, IVec2::NEG_Y];
+
+/// Settings to bake a [`NavMesh`] from level geometry, see [`NavMesh::bake`].
+///
+/// Distances are in world units, and the defaults are for a human sized agent in a level using
+/// meters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BakeSettings {
+    /// Up axis of the level, in world coordinates.
+    pub up: Vec3,
+    /// Size of the voxels along the ground. Smaller voxels are more precise, but slower to bake.
+    pub cell_size: f32,
+    /// Size of the voxels along the up axis.
+    pub cell_height: f32,
+    /// Free height needed above the ground for an agent to stand.
+    pub agent_height: f32,
+    /// Radius of the agents. The walkable area is kept at this distance from walls and ledges.
+    pub agent_radius: f32,
+    /// Maximum height an agent can step up or down, like stairs or curbs.
+    pub step_height: f32,
+    /// Maximum angle in radians between the up axis and a walkable surface.
+    pub max_slope: f32,
+    /// Walkable surfaces with a smaller area are removed, like the top of a table.
+    pub min_region_area: f32,
+}
+
+impl BakeSettings {
+    /// Checks that the voxels have a size, that the up axis has a direction, and that the
+    /// agent settings are not negative.
+    fn check(&self) -> Result<(), NavMeshBuildError> {
+        let positive = |value: f32| value.is_finite() && value > 0.0;
+        if !positive(self.cell_size) {
+            return Err(NavMeshBuildError::InvalidSetting("cell_size"));
+        }
+        if !positive(self.cell_height) {
+            return Err(NavMeshBuildError::InvalidSetting("cell_height"));
+        }
+        for (value, name) in [
+            (self.agent_height, "agent_height"),
+            (self.agent_radius, "agent_radius"),
+            (self.step_height, "step_height"),
+            (self.max_slope, "max_slope"),
+            (self.min_region_area, "min_region_area"),
+        ] {
+            if !value.is_finite() || value < 0.0 {
+                return Err(NavMeshBuildError::InvalidSetting(name));
+            }
+        }
+        if !self.up.is_finite() || self.up == Vec3::ZERO {
+            return Err(NavMeshBuildError::InvalidSetting("up"));
+        }
+        Ok(())
+    }
+}
+
+impl Default for BakeSettings {
+    fn default() -> Self {
+        Self {
+            up: Vec3::Y,
+            cell_size: 0.3,
+            cell_height: 0.2,
+            agent_height: 2.0,
+            agent_radius: 0.6,
+            step_height: 0.9,
+            max_slope: FRAC_PI_4,
+            min_region_area: 1.0,
+        }
+    }
+}
+
+/// A solid part of a column, in voxels from the bottom of the heightfield.
+#[derive(Debug, Clone, Copy)]
+struct Span {
+    bottom: i32,
+    top: i32,
+    /// If an agent can stand on top of the span.
+    walkable: bool,
+}
+
+/// Columns of spans over a grid, in mesh coordinates.
+#[derive(Debug)]
+struct Heightfield {
+    origin: Vec3,
+    size: IVec2,
+    cell_size: f32,
+    cell_height: f32,
+    /// Spans of each column, sorted from the bottom.
+    columns: Vec<Vec<Span>>,
+}
+
+/// The walkable top of a span.
+#[derive(Debug)]
+struct Cell {
+    position: IVec2,
+    floor: i32,
+    ceiling: i32,
+    /// Cell of the neighbouring column that can be walked to, in each of the [`DIRECTIONS`].
+    neighbours: [Option<usize>; 4],
+}
+
+impl Heightfield {
+    fn column(&self, position: IVec2) -> Option<usize> {
+        (position.cmpge(IVec2::ZERO).all() && position.cmplt(self.size).all())
+            .then(|| (position.x + position.y * self.size.x) as usize)
+    }
+
+    /// Bottom of the span above, or where the free space above a span ends.
+    fn ceiling(spans: &[Span], index: usize) -> i32 {
+        spans.get(index + 1).map_or(i32::MAX, |span| span.bottom)
+    }
+
+    /// Adds the parts of a triangle in each column it overlaps.
+    fn rasterize(&mut self, triangle: [Vec3; 3], normal: Vec3, walkable: bool) {
+        let min = triangle[0].min(triangle[1]).min(triangle[2]);
+        let max = triangle[0].max(triangle[1]).max(triangle[2]);
+        let epsilon = self.cell_size * 1e-4;
+        let from = ((min.xy() - self.origin.xy() - epsilon) / self.cell_size)
+            .floor()
+            .as_ivec2()
+            .max(IVec2::ZERO);
+        let to = ((max.xy() - self.origin.xy() + epsilon) / self.cell_size)
+            .floor()
+            .as_ivec2()
+            .min(self.size - 1);
+
+        for y in from.y..=to.y {
+            for x in from.x..=to.x {
+                let cell_min = self.origin.xy() + Vec2::new(x as f32, y as f32) * self.cell_size;
+                let cell_max = cell_min + Vec2::splat(self.cell_size);
+                let mut points = triangle.to_vec();
+                // Slightly larger, for walls on the sides of the column
+                for (axis, value, inside) in [
+                    (0, cell_min.x - epsilon, 1.0),
+                    (1, cell_min.y - epsilon, 1.0),
+                    (0, cell_max.x + epsilon, -1.0),
+                    (1, cell_max.y + epsilon, -1.0),
+                ] {
+                    points = clip(points, axis, value, inside);
+                }
+                if points.is_empty() {
+                    continue;
+                }
+                // Triangles only touching the column on its sides are skipped, except walls
+                // facing out of the column, which are kept in the column behind them
+                let area = points
+                    .iter()
+                    .circular_tuple_windows()
+                    .map(|(a, b)| (a.xy() - cell_min).perp_dot(b.xy() - cell_min))
+                    .sum::<f32>()
+                    / 2.0;
+                if area.abs() <= 4.0 * epsilon * self.cell_size {
+                    let behind = [
+                        (cell_min, 0, -1.0),
+                        (cell_min, 1, -1.0),
+                        (cell_max, 0, 1.0),
+                        (cell_max, 1, 1.0),
+                    ]
+                    .into_iter()
+                    .filter(|(side, axis, _)| {
+                        points
+                            .iter()
+                            .all(|point| (point[*axis] - side[*axis]).abs() <= epsilon)
+                    })
+                    .all(|(_, axis, outward)| normal[axis] * outward > 0.0);
+                    if normal.z.abs() > 1e-6 || !behind {
+                        continue;
+                    }
+                }
+
+                let (low, high) = points
+                    .iter()
+                    .map(|point| point.z)
+                    .minmax()
+                    .into_option()
+                    .unwrap();
+                let bottom = ((low - self.origin.z) / self.cell_height).floor() as i32;
+                let top = ((high - self.origin.z) / self.cell_height).ceil() as i32;
+                self.add_span(
+                    (x + y * self.size.x) as usize,
+                    Span {
+                        bottom,
+                        top: top.max(bottom),
+                        walkable,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Adds a span to a column, merging it with the spans it overlaps.
+    fn add_span(&mut self, column: usize, mut span: Span) {
+        let spans = &mut self.columns[column];
+        let mut index = 0;
+        while index < spans.len() {
+            let current = spans[index];
+            if current.bottom > span.top {
+                break;
+            }
+            if current.top < span.bottom {
+                index += 1;
+                continue;
+            }
+            // The walkable flag is the one of the highest surface
+            span.walkable = match span.top - current.top {
+                difference if difference.abs() <= MERGE_THRESHOLD => {
+                    span.walkable || current.walkable
+                }
+                difference if difference < 0 => current.walkable,
+                _ => span.walkable,
+            };
+            span.bottom = span.bottom.min(current.bottom);
+            span.top = span.top.max(current.top);
+            spans.remove(index);
+        }
+        spans.insert(index, span);
+    }
+
+    /// Obstacles low enough to step on, like curbs, are walkable if the span below is.
+    fn filter_low_obstacles(&mut self, climb: i32) {
+        for spans in &mut self.columns {
+            let mut previous: Option<Span> = None;
+            for span in spans.iter_mut() {
+                let original = *span;
+                if let Some(previous) = previous {
+                    if !span.walkable && previous.walkable && span.top - previous.top <= climb {
+                        span.walkable = true;
+                    }
+                }
+                previous = Some(original);
+            }
+        }
+    }
+
+    /// Spans next to a drop higher than `climb`, or on a slope too steep between their
+    /// neighbours, are not walkable.
+    fn filter_ledges(&mut self, climb: i32, height: i32) {
+        let mut ledges = vec![];
+        for (column, spans) in self.columns.iter().enumerate() {
+            let position = IVec2::new(column as i32 % self.size.x, column as i32 / self.size.x);
+            for (index, span) in spans.iter().enumerate() {
+                if !span.walkable {
+                    continue;
+                }
+                let (floor, ceiling) = (span.top, Self::ceiling(spans, index));
+                let mut lowest = i32::MAX;
+                let (mut accessible_min, mut accessible_max) = (floor, floor);
+
+                let neighbours = DIRECTIONS
+                    .iter()
+                    .filter_map(|direction| self.column(position + *direction));
+                for neighbour in neighbours {
+                    let neighbour_spans = &self.columns[neighbour];
+                    for (neighbour_index, neighbour_span) in neighbour_spans.iter().enumerate() {
+                        let neighbour_floor = neighbour_span.top;
+                        let neighbour_ceiling = Self::ceiling(neighbour_spans, neighbour_index);
+                        if ceiling.min(neighbour_ceiling) - floor.max(neighbour_floor) <= height {
+                            continue;
+                        }
+                        lowest = lowest.min(neighbour_floor - floor);
+                        if (neighbour_floor - floor).abs() <= climb {
+                            accessible_min = accessible_min.min(neighbour_floor);
+                            accessible_max = accessible_max.max(neighbour_floor);
+                        }
+                    }
+                }
+                if lowest < -climb || accessible_max - accessible_min > climb {
+                    ledges.push((column, index));
+                }
+            }
+        }
+        for (column, index) in ledges {
+            self.columns[column][index].walkable = false;
+        }
+    }
+
+    /// Spans without enough free space above them are not walkable.
+    fn filter_low_height(&mut self, height: i32) {
+        for spans in &mut self.columns {
+            for index in 0..spans.len() {
+                if Self::ceiling(spans, index) - spans[index].top < height {
+                    spans[index].walkable = false;
+                }
+            }
+        }
+    }
+
+    /// The walkable spans, connected to their neighbours when an agent can go from one to the
+    /// other.
+    fn cells(&self, climb: i32, height: i32) -> (Vec<Cell>, Vec<Vec<usize>>) {
+        let mut cells = vec![];
+        let mut column_cells = vec![vec![]; self.columns.len()];
+        for (column, spans) in self.columns.iter().enumerate() {
+            for (index, span) in spans.iter().enumerate() {
+                if span.walkable {
+                    column_cells[column].push(cells.len());
+                    cells.push(Cell {
+                        position: IVec2::new(
+                            column as i32 % self.size.x,
+                            column as i32 / self.size.x,
+                        ),
+                        floor: span.top,
+                        ceiling: Self::ceiling(spans, index),
+                        neighbours: [None; 4],
+                    });
+                }
+            }
+        }
+        for index in 0..cells.len() {
+            for (direction, offset) in DIRECTIONS.iter().enumerate() {
+                let Some(neighbour) = self.column(cells[index].position + *offset) else {
+                    continue;
+                };
+                let cell = &cells[index];
+                let connected = column_cells[neighbour].iter().copied().find(|other| {
+                    let other = &cells[*other];
+                    (other.floor - cell.floor).abs() <= climb
+                        && other.ceiling.min(cell.ceiling) - other.floor.max(cell.floor) >= height
+                });
+                cells[index].neighbours[direction] = connected;
+            }
+        }
+        (cells, column_cells)
+    }
+}
+
+/// Clips a polygon to the side of the plane `axis = value` where `inside` is the sign.
+fn clip(points: Vec<Vec3>, axis: usize, value: f32, inside: f32) -> Vec<Vec3> {
+    let distance = |point: Vec3| (point[axis] - value) * inside;
+    points
+        .iter()
+        .circular_tuple_windows()
+        .flat_map(|(a, b)| {
+            let (da, db) = (distance(*a), distance(*b));
+            let crossing = (da * db < 0.0).then(|| a.lerp(*b, da / (da - db)));
+            (da >= 0.0).then_some(*a).into_iter().chain(crossing)
+        })
+        .collect()
+}
+
+/// Cells closer than `radius` cells to the edge of the walkable area, using distances where
+/// a step to a side neighbour is 2 and a step to a diagonal neighbour is 3.
+fn eroded(cells: &[Cell], radius: u32) -> Vec<bool> {
+    let mut distances = vec![u32::MAX; cells.len()];
+    let mut to_visit = BinaryHeap::new();
+    for (index, cell) in cells.iter().enumerate() {
+        if cell.neighbours.iter().any(Option::is_none) {
+            distances[index] = 0;
+            to_visit.push(Reverse((0, index)));
+        }
+    }
+    while let Some(Reverse((distance, index))) = to_visit.pop() {
+        if distance > distances[index] {
+            continue;
+        }
+        for direction in 0..4 {
+            let Some(side) = cells[index].neighbours[direction] else {
+                continue;
+            };
+            let diagonal = cells[side].neighbours[(direction + 1) % 4];
+            for (next, step) in [(Some(side), 2), (diagonal, 3)] {
+                let Some(next) = next else {
+                    continue;
+                };
+                if distance + step < distances[next] {
+                    distances[next] = distance + step;
+                    to_visit.push(Reverse((distance + step, next)));
+                }
+            }
+        }
+    }
+    distances
+        .into_iter()
+        .map(|distance| distance < radius * 2)
+        .collect()
+}
+
+impl NavMesh {
+    /// Bakes a [`NavMesh`] from the geometry of a level, placed in the world by their
+    /// [`GlobalTransform`].
+    ///
+    /// Unlike [`NavMesh::from_bevy_meshes`], the meshes don't need to be the walkable surface:
+    /// walls, floors and props are voxelized, and the surfaces an agent described by `settings`
+    /// can stand and walk on are kept.
+    ///
+    /// A [`NavMesh`] is flat once projected, so where walkable surfaces overlap, like a bridge
+    /// over a road, only the largest connected surface is kept. To support overlapping surfaces,
+    /// bake each of them separately and combine them in a
+    /// [`LayeredNavMesh`](crate::layers::LayeredNavMesh).
+    ///
+    /// Returns [`NavMeshBuildError::InvalidSetting`] if the cell size or height is not a positive
+    /// number or too small for the size of the level, if the up axis is zero, or if one of the
+    /// other settings is negative or not a number.
+    pub fn bake(
+        meshes: &[(&Mesh, GlobalTransform)],
+        settings: &BakeSettings,
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        settings.check()?;
+        let rotation = Quat::from_rotation_arc(settings.up.normalize(), Vec3::Z);
+        let mut triangles = vec![];
+        for (mesh, transform) in meshes {
+            if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+                return Err(NavMeshBuildError::UnsupportedTopology(
+                    mesh.primitive_topology(),
+                ));
+            }
+            let affine = transform.affine();
+            let vertices = get_vectors(mesh, Mesh::ATTRIBUTE_POSITION)?
+                .map(|vertex| rotation * affine.transform_point3(vertex))
+                .collect::<Vec<_>>();
+            let indices = mesh.indices().ok_or(NavMeshBuildError::MissingIndices)?;
+            if let Some(index) = indices.iter().find(|index| *index >= vertices.len()) {
+                return Err(NavMeshBuildError::IndexOutOfBounds(index));
+            }
+            // A mirroring transform reverses the winding order of the triangles
+            let mirrored = affine.matrix3.determinant() < 0.0;
+            triangles.extend(indices.iter().tuples().map(|(a, b, c)| {
+                if mirrored {
+                    [vertices[a], vertices[c], vertices[b]]
+                } else {
+                    [vertices[a], vertices[b], vertices[c]]
+                }
+            }));
+        }
+        let (Some(min), Some(max)) = (
+            triangles.iter().flatten().copied().reduce(Vec3::min),
+            triangles.iter().flatten().copied().reduce(Vec3::max),
+        ) else {
+            return Err(NavMeshBuildError::EmptyMesh);
+        };
+
+        // An empty column on each side, so that walls on the bounds are rasterized
+        let cell_size = settings.cell_size;
+        let origin = min - Vec2::splat(cell_size).extend(0.0);
+        let size = ((max.xy() - origin.xy()) / cell_size).floor() + 2.0;
+        // Columns are indexed with `i32` positions, so there can't be more than `i32::MAX`
+        let columns = (size.x as usize)
+            .checked_mul(size.y as usize)
+            .filter(|columns| *columns <= i32::MAX as usize)
+            .ok_or(NavMeshBuildError::InvalidSetting("cell_size"))?;
+        let size = size.as_ivec2();
+        let mut heightfield = Heightfield {
+            origin,
+            size,
+            cell_size,
+            cell_height: settings.cell_height,
+            columns: vec![vec![]; columns],
+        };
+        let min_cos = settings.max_slope.cos();
+        for triangle in triangles {
+            let [a, b, c] = triangle;
+            let normal = (b - a).cross(c - a).normalize_or_zero();
+            if normal == Vec3::ZERO {
+                continue;
+            }
+            heightfield.rasterize(triangle, normal, normal.z >= min_cos);
+        }
+
+        let climb = (settings.step_height / settings.cell_height).floor() as i32;
+        let height = (settings.agent_height / settings.cell_height).ceil() as i32;
+        heightfield.filter_low_obstacles(climb);
+        heightfield.filter_ledges(climb, height);
+        heightfield.filter_low_height(height);
+
+        let (cells, column_cells) = heightfield.cells(climb, height);
+        let mut kept = eroded(&cells, (settings.agent_radius / cell_size).ceil() as u32)
+            .into_iter()
+            .map(|eroded| !eroded)
+            .collect::<Vec<_>>();
+
+        // Connected surfaces, so that only one is kept in each column
+        let mut components = vec![usize::MAX; cells.len()];
+        let mut sizes = vec![];
+        for start in 0..cells.len() {
+            if !kept[start] || components[start] != usize::MAX {
+                continue;
+            }
+            components[start] = sizes.len();
+            let mut to_visit = vec![start];
+            let mut count = 0;
+            while let Some(cell) = to_visit.pop() {
+                count += 1;
+                for next in cells[cell].neighbours.iter().flatten() {
+                    if kept[*next] && components[*next] == usize::MAX {
+                        components[*next] = sizes.len();
+                        to_visit.push(*next);
+                    }
+                }
+            }
+            sizes.push(count);
+        }
+        let min_cells = settings.min_region_area / (cell_size * cell_size);
+        for (cell, kept) in kept.iter_mut().enumerate() {
+            *kept = *kept && sizes[components[cell]] as f32 >= min_cells;
+        }
+        let mut occupied: Vec<Option<usize>> = vec![None; column_cells.len()];
+        let by_surface_size = (0..cells.len())
+            .filter(|cell| kept[*cell])
+            .sorted_by_key(|cell| (Reverse(sizes[components[*cell]]), components[*cell]));
+        for cell in by_surface_size {
+            let column = heightfield.column(cells[cell].position).unwrap();
+            if occupied[column].is_none() {
+                occupied[column] = Some(cell);
+            } else {
+                kept[cell] = false;
+            }
+        }
+
+        // Cells next to each other but not connected, like the bottom and the top of a cliff,
+        // would be merged once projected. The higher one is removed.
+        let mut removed = vec![];
+        for (index, cell) in cells.iter().enumerate().filter(|(index, _)| kept[*index]) {
+            for (direction, offset) in DIRECTIONS.iter().enumerate() {
+                let Some(other) = heightfield
+                    .column(cell.position + *offset)
+                    .and_then(|column| occupied[column])
+                else {
+                    continue;
+                };
+                if cell.neighbours[direction] != Some(other) {
+                    removed.push(if (cell.floor, index) > (cells[other].floor, other) {
+                        index
+                    } else {
+                        other
+                    });
+                }
+            }
+        }
+        removed.into_iter().for_each(|cell| kept[cell] = false);
+
+        // Rows of walkable cells, merged in the walkable area
+        let corner = |position: IVec2| origin.xy() + position.as_vec2() * cell_size;
+        let mut rows = vec![];
+        for y in 0..size.y {
+            let walkable = (0..size.x)
+                .map(|x| occupied[(x + y * size.x) as usize].is_some_and(|cell| kept[cell]))
+                .collect::<Vec<_>>();
+            for (is_walkable, mut run) in &walkable.iter().enumerate().group_by(|(_, w)| **w) {
+                if !is_walkable {
+                    continue;
+                }
+                let start = run.next().unwrap().0 as i32;
+                let end = run.last().map_or(start, |(x, _)| x as i32) + 1;
+                rows.push(to_polygon(&[
+                    corner(IVec2::new(start, y)),
+                    corner(IVec2::new(end, y)),
+                    corner(IVec2::new(end, y + 1)),
+                    corner(IVec2::new(start, y + 1)),
+                ]));
+            }
+        }
+        let mesh = triangulate(&union_all(rows), cell_size * cell_size)?;
+
+        // Heights at the corners of the cells, averaged between the cells of a surface
+        let mut corners: HashMap<(IVec2, usize), (f32, f32)> = HashMap::new();
+        for (index, cell) in cells.iter().enumerate().filter(|(index, _)| kept[*index]) {
+            let floor = origin.z + cell.floor as f32 * settings.cell_height;
+            for offset in [IVec2::ZERO, IVec2::X, IVec2::ONE, IVec2::Y] {
+                let (sum, count) = corners
+                    .entry((cell.position + offset, components[index]))
+                    .or_default();
+                *sum += floor;
+                *count += 1.0;
+            }
+        }
+        let mut vertices = vec![];
+        let mut vertex_indices = HashMap::new();
+        let mut height_triangles = vec![];
+        for (index, cell) in cells.iter().enumerate().filter(|(index, _)| kept[*index]) {
+            let [a, b, c, d] = [IVec2::ZERO, IVec2::X, IVec2::ONE, IVec2::Y].map(|offset| {
+                let key = (cell.position + offset, components[index]);
+                *vertex_indices.entry(key).or_insert_with(|| {
+                    let (sum, count) = corners[&key];
+                    vertices.push(corner(key.0).extend(sum / count));
+                    vertices.len() - 1
+                })
+            });
+            height_triangles.extend([[a, b, c], [a, c, d]]);
+        }
+        let heights = vertices
+            .iter()
+            .any(|vertex| vertex.z.abs() > WELDING_DISTANCE)
+            .then(|| Arc::new(HeightMap::new(vertices, height_triangles)));
+
+        let mut navmesh = NavMesh::from_polyanya_mesh(mesh);
+        navmesh.transform = Transform::from_rotation(rotation);
+        navmesh.heights = heights;
+        navmesh.agent_radius = settings.agent_radius;
+        Ok(navmesh)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::{mesh::Indices, render_asset::RenderAssetUsages};
+
+    use super::*;
+
+    /// A quad with its corners in counter clockwise order seen from its front
+    fn quad(corners: [Vec3; 4]) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, corners.to_vec());
+        mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+        mesh
+    }
+
+    /// A horizontal quad facing up, from `min` to `max` on the x and -z axis
+    fn ground(min: Vec2, max: Vec2, y: f32) -> Mesh {
+        quad([
+            Vec3::new(min.x, y, -min.y),
+            Vec3::new(max.x, y, -min.y),
+            Vec3::new(max.x, y, -max.y),
+            Vec3::new(min.x, y, -max.y),
+        ])
+    }
+
+    fn settings() -> BakeSettings {
+        BakeSettings {
+            cell_size: 0.25,
+            ..default()
+        }
+    }
+
+    #[test]
+    fn baked_floor_keeps_clear_of_walls() {
+        let floor = ground(Vec2::ZERO, Vec2::splat(10.0), 0.0);
+        let block = Mesh::from(Cuboid::new(2.0, 3.0, 2.0));
+        let navmesh = NavMesh::bake(
+            &[
+                (&floor, GlobalTransform::IDENTITY),
+                (
+                    &block,
+                    GlobalTransform::from_translation(Vec3::new(5.0, 1.5, -5.0)),
+                ),
+            ],
+            &settings(),
+        )
+        .unwrap();
+
+        assert!(navmesh.transformed_is_in_mesh(Vec3::new(2.0, 0.0, -2.0)));
+        assert!(navmesh.transformed_is_in_mesh(Vec3::new(2.5, 0.0, -5.0)));
+        // In the block, close to it or close to the edge of the floor
+        assert!(!navmesh.transformed_is_in_mesh(Vec3::new(5.0, 0.0, -5.0)));
+        assert!(!navmesh.transformed_is_in_mesh(Vec3::new(3.7, 0.0, -5.0)));
+        assert!(!navmesh.transformed_is_in_mesh(Vec3::new(0.3, 0.0, -5.0)));
+
+        let path = navmesh
+            .transformed_path(Vec3::new(2.0, 0.0, -5.0), Vec3::new(8.0, 0.0, -5.0))
+            .unwrap();
+        assert!(path.length > 6.5);
+        assert!(path
+            .path
+            .iter()
+            .all(|point| point.x < 3.4 || point.x > 6.6 || (point.z + 5.0).abs() > 1.4));
+    }
+
+    #[test]
+    fn slopes_and_ceilings_limit_the_walkable_area() {
+        let floor = ground(Vec2::ZERO, Vec2::splat(10.0), 0.0);
+        // Low enough that agents can't stand below it
+        let ceiling = quad([
+            Vec3::new(6.0, 1.0, -6.0),
+            Vec3::new(6.0, 1.0, -9.0),
+            Vec3::new(9.0, 1.0, -9.0),
+            Vec3::new(9.0, 1.0, -6.0),
+        ]);
+        let gentle_ramp = quad([
+            Vec3::new(10.0, 0.0, 0.0),
+            Vec3::new(14.0, 1.0, 0.0),
+            Vec3::new(14.0, 1.0, -4.0),
+            Vec3::new(10.0, 0.0, -4.0),
+        ]);
+        let low_platform = ground(Vec2::new(14.0, 0.0), Vec2::new(18.0, 4.0), 1.0);
+        let steep_ramp = quad([
+            Vec3::new(10.0, 0.0, -6.0),
+            Vec3::new(11.0, 3.0, -6.0),
+            Vec3::new(11.0, 3.0, -10.0),
+            Vec3::new(10.0, 0.0, -10.0),
+        ]);
+        let high_platform = ground(Vec2::new(11.0, 6.0), Vec2::new(15.0, 10.0), 3.0);
+        let meshes = [
+            &floor,
+            &ceiling,
+            &gentle_ramp,
+            &low_platform,
+            &steep_ramp,
+            &high_platform,
+        ]
+        .map(|mesh| (mesh, GlobalTransform::IDENTITY));
+        let navmesh = NavMesh::bake(&meshes, &settings()).unwrap();
+
+        assert!(!navmesh.transformed_is_in_mesh(Vec3::new(7.5, 0.0, -7.5)));
+        assert!(navmesh.transformed_is_in_mesh(Vec3::new(4.0, 0.0, -7.5)));
+
+        let path = navmesh
+            .transformed_path(Vec3::new(2.0, 0.0, -2.0), Vec3::new(16.0, 1.0, -2.0))
+            .unwrap();
+        let end = path.path.last().unwrap();
+        assert!((end.y - 1.0).abs() <= settings().cell_height, "{end}");
+
+        let start = Vec3::new(2.0, 0.0, -8.0);
+        let top = Vec3::new(13.0, 3.0, -8.0);
+        assert!(navmesh.transformed_island_of(top).is_some());
+        assert!(!navmesh.transformed_is_reachable(start, top));
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let floor = ground(Vec2::ZERO, Vec2::splat(10.0), 0.0);
+        let meshes = [(&floor, GlobalTransform::IDENTITY)];
+        for (invalid, name) in [
+            (
+                BakeSettings {
+                    cell_size: 0.0,
+                    ..settings()
+                },
+                "cell_size",
+            ),
+            (
+                BakeSettings {
+                    cell_height: f32::NAN,
+                    ..settings()
+                },
+                "cell_height",
+            ),
+            (
+                BakeSettings {
+                    up: Vec3::ZERO,
+                    ..settings()
+                },
+                "up",
+            ),
+            (
+                BakeSettings {
+                    agent_height: -1.0,
+                    ..settings()
+                },
+                "agent_height",
+            ),
+            (
+                BakeSettings {
+                    agent_radius: f32::NAN,
+                    ..settings()
+                },
+                "agent_radius",
+            ),
+            (
+                BakeSettings {
+                    step_height: -0.5,
+                    ..settings()
+                },
+                "step_height",
+            ),
+            (
+                BakeSettings {
+                    max_slope: f32::NAN,
+                    ..settings()
+                },
+                "max_slope",
+            ),
+            (
+                BakeSettings {
+                    min_region_area: f32::INFINITY,
+                    ..settings()
+                },
+                "min_region_area",
+            ),
+            (
+                BakeSettings {
+                    cell_size: 1e-5,
+                    ..settings()
+                },
+                "cell_size",
+            ),
+        ] {
+            assert_eq!(
+                NavMesh::bake(&meshes, &invalid).unwrap_err(),
+                NavMeshBuildError::InvalidSetting(name)
+            );
+        }
+    }
+
+    /// A heightfield with a single walkable span in each column, at the given floors
+    fn flat_heightfield(size: IVec2, floors: &[i32]) -> Heightfield {
+        Heightfield {
+            origin: Vec3::ZERO,
+            size,
+            cell_size: 1.0,
+            cell_height: 1.0,
+            columns: floors
+                .iter()
+                .map(|floor| {
+                    vec![Span {
+                        bottom: floor - 1,
+                        top: *floor,
+                        walkable: true,
+                    }]
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn spans_next_to_a_drop_are_ledges() {
+        let mut heightfield = flat_heightfield(IVec2::new(4, 1), &[10, 10, 0, 1]);
+        heightfield.filter_ledges(2, 5);
+        let walkable = heightfield
+            .columns
+            .iter()
+            .map(|spans| spans[0].walkable)
+            .collect::<Vec<_>>();
+        assert_eq!(walkable, vec![true, false, true, true]);
+    }
+
+    #[test]
+    fn spans_below_a_low_ceiling_are_not_walkable() {
+        let mut heightfield = flat_heightfield(IVec2::ONE, &[5]);
+        heightfield.columns[0].push(Span {
+            bottom: 10,
+            top: 12,
+            walkable: true,
+        });
+        heightfield.filter_low_height(8);
+        assert!(!heightfield.columns[0][0].walkable);
+        assert!(heightfield.columns[0][1].walkable);
+
+        let mut heightfield = flat_heightfield(IVec2::ONE, &[5]);
+        heightfield.columns[0].push(Span {
+            bottom: 13,
+            top: 14,
+            walkable: true,
+        });
+        heightfield.filter_low_height(8);
+        assert!(heightfield.columns[0][0].walkable);
+    }
+
+    #[test]
+    fn erosion_removes_cells_close_to_the_edge() {
+        let heightfield = flat_heightfield(IVec2::splat(5), &[0; 25]);
+        let (cells, _) = heightfield.cells(1, 2);
+        let center = IVec2::splat(2);
+
+        let eroded_once = eroded(&cells, 1);
+        for (cell, eroded) in cells.iter().zip(eroded_once) {
+            let on_edge = cell.position.min_element() == 0 || cell.position.max_element() == 4;
+            assert_eq!(eroded, on_edge, "{}", cell.position);
+        }
+        let eroded_twice = eroded(&cells, 2);
+        for (cell, eroded) in cells.iter().zip(eroded_twice) {
+            assert_eq!(eroded, cell.position != center, "{}", cell.position);
+        }
+    }
+}
@@ -0,0 +1,451 @@
+//! Build a [`NavMesh`] from polygons, triangulating the walkable area
+
+use std::{f32::consts::TAU, iter, sync::Arc};
+
+use bevy::prelude::*;
+use geo::{BooleanOps, Coord, LineString, MultiPolygon, Polygon};
+use polyanya::Triangulation;
+
+use crate::{links::OffMeshLink, NavMesh, NavMeshBuildError};
+
+/// Builds a [`NavMesh`] from an outer boundary and a list of obstacles.
+///
+/// The walkable area, inside the boundary and outside the obstacles, is triangulated using a
+/// constrained Delaunay triangulation, then the triangles are merged into larger convex polygons.
+/// Obstacles can overlap each other and cross the boundary.
+///
+/// ```
+/// # use bevy::prelude::*;
+/// # use vleue_navigator::builder::NavMeshBuilder;
+/// let navmesh = NavMeshBuilder::new(vec![
+///     Vec2::new(0.0, 0.0),
+///     Vec2::new(10.0, 0.0),
+///     Vec2::new(10.0, 10.0),
+///     Vec2::new(0.0, 10.0),
+/// ])
+/// .with_obstacle(vec![
+///     Vec2::new(4.0, 4.0),
+///     Vec2::new(6.0, 4.0),
+///     Vec2::new(6.0, 6.0),
+///     Vec2::new(4.0, 6.0),
+/// ])
+/// .build()
+/// .unwrap();
+///
+/// assert!(navmesh.is_in_mesh(Vec2::new(2.0, 2.0)));
+/// assert!(!navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct NavMeshBuilder {
+    outer: Vec<Vec2>,
+    obstacles: Vec<Vec<Vec2>>,
+    simplify: f32,
+    agent_radius: f32,
+}
+
+/// Number of segments used to approximate a full circle when rounding corners.
+const ROUND_SEGMENTS: usize = 16;
+
+impl NavMeshBuilder {
+    /// Starts building a [`NavMesh`] from the points of its outer boundary.
+    pub fn new(outer: Vec<Vec2>) -> Self {
+        Self {
+            outer,
+            ..Default::default()
+        }
+    }
+
+    /// Adds an obstacle delimited by the list of points on its edges.
+    pub fn with_obstacle(mut self, obstacle: Vec<Vec2>) -> Self {
+        self.obstacles.push(obstacle);
+        self
+    }
+
+    /// Adds obstacles delimited by the list of points on their edges.
+    pub fn with_obstacles(mut self, obstacles: impl IntoIterator<Item = Vec<Vec2>>) -> Self {
+        self.obstacles.extend(obstacles);
+        self
+    }
+
+    /// Simplifies the boundary and the obstacles before triangulation.
+    ///
+    /// `epsilon` is the minimum area a point should contribute to a polygon to be kept.
+    pub fn simplify(mut self, epsilon: f32) -> Self {
+        self.simplify = epsilon;
+        self
+    }
+
+    /// Keeps the walkable area at `radius` from the boundary and the obstacles, so that paths are
+    /// valid for an agent of that radius.
+    pub fn agent_radius(mut self, radius: f32) -> Self {
+        self.agent_radius = radius;
+        self
+    }
+
+    /// Builds the [`NavMesh`].
+    ///
+    /// Returns [`NavMeshBuildError::InvalidSetting`] if the agent radius is negative or not a
+    /// number.
+    pub fn build(&self) -> Result<NavMesh, NavMeshBuildError> {
+        if !self.agent_radius.is_finite() || self.agent_radius < 0.0 {
+            return Err(NavMeshBuildError::InvalidSetting("agent_radius"));
+        }
+        let outer = MultiPolygon(vec![to_polygon(&self.outer)]);
+        let obstacles = union_all(self.obstacles.iter().map(|obstacle| to_polygon(obstacle)));
+        let mut walkable = outer.difference(&obstacles);
+        if self.agent_radius > 0.0 {
+            walkable = walkable.difference(&edges_buffer(&walkable, self.agent_radius));
+        }
+        let mesh = triangulate(&walkable, self.simplify)?;
+        let mut navmesh = NavMesh::from_polyanya_mesh(mesh);
+        navmesh.agent_radius = self.agent_radius;
+        Ok(navmesh)
+    }
+}
+
+impl NavMesh {
+    /// Creates a [`NavMesh`] for agents of the given `radius`, in mesh coordinates.
+    ///
+    /// The walkable area is kept at `radius` from the edges of this mesh, so that paths don't get
+    /// closer than that to a wall. Obstacles already cut out of this mesh are ignored, and those
+    /// cut out of the new mesh are inflated by `radius`. To support several agent sizes on the
+    /// same level, create one [`NavMesh`] per radius from the same source.
+    ///
+    /// The ends of off-mesh links are moved to the closest point of the new mesh, and links with
+    /// an end further than `link_snap_distance` from it, where the mesh was too narrow for the
+    /// agent, are dropped. Ends along an edge move by up to `radius`, and by more in corners: up
+    /// to `radius * SQRT_2` in a right angle.
+    ///
+    /// Returns [`NavMeshBuildError::InvalidSetting`] if `radius` is not a positive number, or if
+    /// `link_snap_distance` is negative.
+    pub fn eroded(
+        &self,
+        radius: f32,
+        link_snap_distance: f32,
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        if !(radius.is_finite() && radius > 0.0) {
+            return Err(NavMeshBuildError::InvalidSetting("radius"));
+        }
+        if link_snap_distance.is_nan() || link_snap_distance < 0.0 {
+            return Err(NavMeshBuildError::InvalidSetting("link_snap_distance"));
+        }
+        let (base, area) = match &self.uncut {
+            Some(uncut) => (&uncut.mesh, (*uncut.area).clone()),
+            None => (&self.mesh, mesh_area(&self.mesh)),
+        };
+        let mut mesh = triangulate(&area.difference(&edges_buffer(&area, radius)), 0.0)?;
+        mesh.set_delta(base.delta());
+        let mut eroded = NavMesh {
+            uncut: None,
+            agent_radius: self.agent_radius + radius,
+            links: vec![],
+            ..self.clone()
+        };
+        eroded.set_mesh(Arc::new(mesh));
+        let snap = |point| eroded.closest_point_within(point, link_snap_distance);
+        // The links as they were added, before being moved out of obstacles
+        let links = self
+            .uncut
+            .as_ref()
+            .map_or(&self.links, |uncut| &uncut.links);
+        let links = links
+            .iter()
+            .filter_map(|link| {
+                Some(OffMeshLink {
+                    start: snap(link.start)?,
+                    end: snap(link.end)?,
+                    ..*link
+                })
+            })
+            .collect::<Vec<_>>();
+        for link in links {
+            eroded.add_link(link);
+        }
+        Ok(eroded)
+    }
+}
+
+/// The area covered by the polygons of a mesh.
+pub(crate) fn mesh_area(mesh: &polyanya::Mesh) -> MultiPolygon<f32> {
+    union_all(mesh.polygons.iter().map(|polygon| {
+        to_polygon(
+            &polygon
+                .vertices
+                .iter()
+                .map(|vertex| mesh.vertices[*vertex as usize].coords)
+                .collect::<Vec<_>>(),
+        )
+    }))
+}
+
+/// The area closer than `radius` to the edges of `area`, on both sides.
+pub(crate) fn edges_buffer(area: &MultiPolygon<f32>, radius: f32) -> MultiPolygon<f32> {
+    // Polygons around the corners are circumscribed to the circle, so that it's fully covered
+    let corner_radius = radius / (TAU / ROUND_SEGMENTS as f32 / 2.0).cos();
+    let corner = (0..ROUND_SEGMENTS)
+        .map(|i| Vec2::from_angle(i as f32 * TAU / ROUND_SEGMENTS as f32) * corner_radius)
+        .collect::<Vec<_>>();
+
+    let rings = area
+        .iter()
+        .flat_map(|polygon| iter::once(polygon.exterior()).chain(polygon.interiors()));
+    union_all(rings.flat_map(|ring| ring.lines()).flat_map(|line| {
+        let start = Vec2::new(line.start.x, line.start.y);
+        let end = Vec2::new(line.end.x, line.end.y);
+        let side = (end - start).normalize_or_zero().perp() * radius;
+        [
+            to_polygon(&[start - side, end - side, end + side, start + side]),
+            to_polygon(&corner.iter().map(|p| *p + start).collect::<Vec<_>>()),
+        ]
+    }))
+}
+
+/// Merges polygons into a single area, pairwise to keep intermediate areas small.
+pub(crate) fn union_all(polygons: impl IntoIterator<Item = Polygon<f32>>) -> MultiPolygon<f32> {
+    let mut areas: Vec<MultiPolygon<f32>> = polygons
+        .into_iter()
+        .map(|polygon| MultiPolygon(vec![polygon]))
+        .collect();
+    while areas.len() > 1 {
+        areas = areas
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] => a.union(b),
+                [a] => a.clone(),
+                _ => unreachable!(),
+            })
+            .collect();
+    }
+    areas.pop().unwrap_or(MultiPolygon(vec![]))
+}
+
+pub(crate) fn to_polygon(points: &[Vec2]) -> Polygon<f32> {
+    Polygon::new(
+        LineString(points.iter().map(|p| Coord { x: p.x, y: p.y }).collect()),
+        vec![],
+    )
+}
+
+fn to_points(line: &LineString<f32>) -> Vec<Vec2> {
+    // Polygons rings are closed, with the first point repeated at the end
+    let mut points: Vec<Vec2> = line.coords().map(|c| Vec2::new(c.x, c.y)).collect();
+    if points.len() > 1 && points.first() == points.last() {
+        points.pop();
+    }
+    points
+}
+
+/// Triangulates a walkable area, then merges the triangles into convex polygons.
+///
+/// Each polygon of the area is triangulated on its own, then they are gathered in a single mesh.
+pub(crate) fn triangulate(
+    walkable: &MultiPolygon<f32>,
+    simplify: f32,
+) -> Result<polyanya::Mesh, NavMeshBuildError> {
+    let mut vertices: Vec<polyanya::Vertex> = vec![];
+    let mut polygons: Vec<polyanya::Polygon> = vec![];
+
+    for polygon in walkable.iter() {
+        let mut triangulation = Triangulation::from_outer_edges(&to_points(polygon.exterior()));
+        triangulation.add_obstacles(polygon.interiors().iter().map(to_points));
+        if simplify > 0.0 {
+            triangulation.simplify(simplify);
+        }
+        let mesh = triangulation
+            .as_navmesh()
+            .ok_or(NavMeshBuildError::InvalidPolygons)?;
+
+        let vertex_offset = vertices.len() as u32;
+        let polygon_offset = polygons.len() as isize;
+        vertices.extend(mesh.vertices.into_iter().map(|vertex| {
+            polyanya::Vertex::new(
+                vertex.coords,
+                vertex
+                    .polygons
+                    .into_iter()
+                    .map(|p| if p == -1 { -1 } else { p + polygon_offset })
+                    .collect(),
+            )
+        }));
+        polygons.extend(mesh.polygons.into_iter().map(|polygon| {
+            polyanya::Polygon::new(
+                polygon
+                    .vertices
+                    .into_iter()
+                    .map(|v| v + vertex_offset)
+                    .collect(),
+                polygon.is_one_way,
+            )
+        }));
+    }
+    if polygons.is_empty() {
+        return Err(NavMeshBuildError::EmptyMesh);
+    }
+
+    let mut mesh = polyanya::Mesh::new(vertices, polygons);
+    mesh.merge_polygons();
+    mesh.bake();
+    Ok(mesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{square, square_with_obstacle};
+
+    #[test]
+    fn path_goes_around_obstacle() {
+        let navmesh = NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.0)))
+            .with_obstacle(square(Vec2::new(2.0, 2.0), Vec2::new(8.0, 8.0)))
+            .build()
+            .unwrap();
+
+        assert!(!navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+        let path = navmesh
+            .path(Vec2::new(1.0, 1.0), Vec2::new(9.0, 9.0))
+            .unwrap();
+        assert_eq!(path.path.len(), 2);
+        assert!(path.path[0] == Vec2::new(8.0, 2.0) || path.path[0] == Vec2::new(2.0, 8.0));
+    }
+
+    #[test]
+    fn overlapping_obstacles_are_merged() {
+        let navmesh = NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.0)))
+            .with_obstacles([
+                square(Vec2::new(2.0, 2.0), Vec2::new(5.0, 5.0)),
+                square(Vec2::new(4.0, 4.0), Vec2::new(7.0, 7.0)),
+                // Crosses the boundary
+                square(Vec2::new(8.0, -1.0), Vec2::new(11.0, 1.0)),
+            ])
+            .build()
+            .unwrap();
+
+        assert!(!navmesh.is_in_mesh(Vec2::new(3.0, 3.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(6.0, 6.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(9.0, 0.5)));
+        assert!(navmesh.is_in_mesh(Vec2::new(9.0, 1.5)));
+        assert!(navmesh
+            .path(Vec2::new(1.0, 9.0), Vec2::new(9.0, 1.5))
+            .is_some());
+    }
+
+    #[test]
+    fn path_keeps_clear_of_obstacle_with_agent_radius() {
+        let navmesh = NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.0)))
+            .with_obstacle(square(Vec2::new(2.0, 2.0), Vec2::new(8.0, 8.0)))
+            .agent_radius(0.5)
+            .build()
+            .unwrap();
+
+        assert!(!navmesh.is_in_mesh(Vec2::new(0.2, 5.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(1.8, 5.0)));
+        let path = navmesh
+            .path(Vec2::new(1.0, 1.0), Vec2::new(9.0, 9.0))
+            .unwrap();
+        for point in path.path {
+            let clearance = point.distance(point.clamp(Vec2::splat(2.0), Vec2::splat(8.0)));
+            assert!(clearance > 0.49, "{point} is too close to the obstacle");
+        }
+    }
+
+    #[test]
+    fn eroded_navmesh_for_several_radii() {
+        let navmesh = NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.0)))
+            .build()
+            .unwrap();
+
+        for radius in [0.5, 1.0, 2.0] {
+            let eroded = navmesh.eroded(radius, radius).unwrap();
+            assert_eq!(eroded.agent_radius(), radius);
+            // Points are considered in the mesh up to the mesh delta
+            assert!(!eroded.is_in_mesh(Vec2::new(radius - 0.2, 5.0)));
+            assert!(eroded.is_in_mesh(Vec2::new(radius + 0.01, 5.0)));
+        }
+        assert!(navmesh.is_in_mesh(Vec2::new(0.1, 5.0)));
+    }
+
+    #[test]
+    fn eroded_navmesh_snaps_or_drops_links() {
+        // A room with a corridor too narrow for the eroded mesh
+        let mut navmesh = NavMeshBuilder::new(vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(10.0, 0.0),
+            Vec2::new(10.0, 1.0),
+            Vec2::new(4.0, 1.0),
+            Vec2::new(4.0, 10.0),
+            Vec2::new(0.0, 10.0),
+        ])
+        .build()
+        .unwrap();
+        let link = OffMeshLink {
+            start: Vec2::new(0.1, 5.0),
+            end: Vec2::new(3.9, 9.9),
+            bidirectional: false,
+            cost: 1.0,
+            tag: 1,
+        };
+        navmesh.add_link(link);
+        navmesh.add_link(OffMeshLink {
+            start: Vec2::new(2.0, 5.0),
+            end: Vec2::new(9.0, 0.5),
+            tag: 2,
+            ..link
+        });
+
+        let eroded = navmesh.eroded(1.0, 1.5).unwrap();
+        assert!(!eroded.is_in_mesh(Vec2::new(9.0, 0.5)));
+        assert_eq!(eroded.links().len(), 1);
+        let snapped = eroded.links()[0];
+        assert_eq!(snapped.tag, 1);
+        assert!(snapped.start.distance(Vec2::new(1.0, 5.0)) < 0.01);
+        assert!(snapped.end.distance(Vec2::new(3.0, 9.0)) < 0.01);
+        assert!(eroded.is_in_mesh(snapped.start));
+        assert!(eroded.is_in_mesh(snapped.end));
+        assert_eq!(navmesh.links().len(), 2);
+
+        // The end in the corner is about 1.27 away from the eroded mesh
+        let eroded = navmesh.eroded(1.0, 1.0).unwrap();
+        assert!(eroded.links().is_empty());
+    }
+
+    #[test]
+    fn eroded_navmesh_needs_a_radius() {
+        let navmesh = NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.0)))
+            .build()
+            .unwrap();
+        for radius in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                navmesh.eroded(radius, 1.0).unwrap_err(),
+                NavMeshBuildError::InvalidSetting("radius")
+            );
+        }
+        assert_eq!(
+            navmesh.eroded(1.0, -1.0).unwrap_err(),
+            NavMeshBuildError::InvalidSetting("link_snap_distance")
+        );
+    }
+
+    #[test]
+    fn agent_radius_must_not_be_negative() {
+        for radius in [-1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.0)))
+                    .agent_radius(radius)
+                    .build()
+                    .unwrap_err(),
+                NavMeshBuildError::InvalidSetting("agent_radius")
+            );
+        }
+    }
+
+    #[test]
+    fn obstacle_splitting_the_mesh() {
+        let navmesh = square_with_obstacle(Vec2::new(4.0, -1.0), Vec2::new(6.0, 11.0));
+
+        assert!(navmesh.is_in_mesh(Vec2::new(1.0, 1.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(9.0, 1.0)));
+        assert!(navmesh
+            .path(Vec2::new(1.0, 1.0), Vec2::new(9.0, 1.0))
+            .is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! Build a [`NavMesh`] from a grid of walkable tiles, for tile based games
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use geo::{Contains, Coord, LineString, MultiPolygon, Polygon};
+
+use crate::{builder::triangulate, NavMesh, NavMeshBuildError};
+
+impl NavMesh {
+    /// Creates a [`NavMesh`] from a grid of `width` by `height` tiles of `cell_size`, where
+    /// `walkable(x, y)` tells if the tile at column `x` and row `y` can be walked on.
+    ///
+    /// The tile `(x, y)` covers the square from `(x, y) * cell_size` to
+    /// `(x + 1, y + 1) * cell_size` in mesh coordinates. Walkable tiles are merged into large
+    /// convex polygons, so that paths are not constrained by the tiles. Tiles that only touch by
+    /// a corner are not connected.
+    ///
+    /// Returns [`NavMeshBuildError::InvalidSetting`] if `cell_size` is not a positive number.
+    pub fn from_grid(
+        width: u32,
+        height: u32,
+        cell_size: f32,
+        walkable: impl Fn(u32, u32) -> bool,
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        if !cell_size.is_finite() || cell_size <= 0.0 {
+            return Err(NavMeshBuildError::InvalidSetting("cell_size"));
+        }
+        let mesh = triangulate(&grid_area(width, height, cell_size, walkable), 0.0)?;
+        Ok(NavMesh::from_polyanya_mesh(mesh))
+    }
+}
+
+/// The area covered by the walkable cells of a grid, as polygons with holes.
+///
+/// The contours are traced along the sides of the cells, keeping only the corners. Cells that
+/// only touch by a corner are in different polygons.
+pub(crate) fn grid_area(
+    width: u32,
+    height: u32,
+    cell_size: f32,
+    walkable: impl Fn(u32, u32) -> bool,
+) -> MultiPolygon<f32> {
+    let cells = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| walkable(x, y))
+        .collect::<Vec<_>>();
+    let is_walkable = |cell: IVec2| {
+        cell.cmpge(IVec2::ZERO).all()
+            && cell.cmplt(IVec2::new(width as i32, height as i32)).all()
+            && cells[(cell.x + cell.y * width as i32) as usize]
+    };
+
+    // Sides between a walkable cell and a blocked one, as a corner and a direction, with the
+    // walkable cell on their left
+    let mut sides = vec![];
+    for y in 0..height as i32 {
+        for x in 0..width as i32 {
+            let cell = IVec2::new(x, y);
+            if !is_walkable(cell) {
+                continue;
+            }
+            for (neighbour, start, direction) in [
+                (IVec2::NEG_Y, IVec2::ZERO, IVec2::X),
+                (IVec2::X, IVec2::X, IVec2::Y),
+                (IVec2::Y, IVec2::ONE, IVec2::NEG_X),
+                (IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y),
+            ] {
+                if !is_walkable(cell + neighbour) {
+                    sides.push((cell + start, direction));
+                }
+            }
+        }
+    }
+    let mut outgoing: HashMap<IVec2, Vec<IVec2>> = HashMap::new();
+    for (corner, direction) in &sides {
+        outgoing.entry(*corner).or_default().push(*direction);
+    }
+
+    // Contours follow the sides, turning left when two cells touch by a corner so that they
+    // stay apart. Exteriors are counterclockwise and holes are clockwise.
+    let mut visited = HashSet::new();
+    let mut exteriors = vec![];
+    let mut holes = vec![];
+    for first in &sides {
+        if visited.contains(first) {
+            continue;
+        }
+        let (mut corner, mut direction) = *first;
+        let mut points = vec![];
+        loop {
+            visited.insert((corner, direction));
+            corner += direction;
+            let next = [direction.perp(), direction, -direction.perp()]
+                .into_iter()
+                .find(|next| outgoing[&corner].contains(next))
+                .unwrap();
+            if next != direction {
+                points.push(corner);
+            }
+            if (corner, next) == *first {
+                break;
+            }
+            direction = next;
+        }
+        let area = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.perp_dot(*b) as i64)
+            .sum::<i64>();
+        let ring = LineString(
+            points
+                .iter()
+                .map(|point| Coord {
+                    x: point.x as f32 * cell_size,
+                    y: point.y as f32 * cell_size,
+                })
+                .collect(),
+        );
+        if area > 0 {
+            exteriors.push((area, Polygon::new(ring, vec![])));
+        } else {
+            // A point in a walkable cell next to the hole, to find the exterior around it
+            let (a, b) = (points[0].as_vec2(), points[1].as_vec2());
+            let inside = ((a + b) / 2.0 + (b - a).normalize().perp() / 4.0) * cell_size;
+            holes.push((ring, Coord::from((inside.x, inside.y))));
+        }
+    }
+
+    // Holes belong to the smallest exterior around them
+    exteriors.sort_by_key(|(area, _)| *area);
+    for (hole, inside) in holes {
+        if let Some((_, exterior)) = exteriors
+            .iter_mut()
+            .find(|(_, exterior)| exterior.contains(&inside))
+        {
+            exterior.interiors_push(hole);
+        }
+    }
+    MultiPolygon(exteriors.into_iter().map(|(_, polygon)| polygon).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAZE: [&str; 6] = [
+        "#########",
+        "#...#...#",
+        "#.#.#.#.#",
+        "#.#...#.#",
+        "#.#####.#",
+        "#########",
+    ];
+
+    fn maze() -> NavMesh {
+        NavMesh::from_grid(9, 6, 2.0, |x, y| {
+            MAZE[y as usize].as_bytes()[x as usize] == b'.'
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn grid_tiles_are_merged() {
+        let navmesh = maze();
+        let mesh = navmesh.get();
+
+        let tiles = MAZE
+            .iter()
+            .map(|row| row.matches('.').count())
+            .sum::<usize>();
+        assert!(mesh.polygons.len() < tiles / 2, "{}", mesh.polygons.len());
+        assert!(navmesh.is_in_mesh(Vec2::new(3.0, 3.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(7.0, 7.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(1.0, 1.0)));
+    }
+
+    #[test]
+    fn path_through_grid() {
+        let navmesh = maze();
+
+        let path = navmesh
+            .path(Vec2::new(3.0, 9.0), Vec2::new(15.0, 9.0))
+            .unwrap();
+        // Along the first corridor, back through the middle one and along the last one
+        assert!(path.length > 20.0, "{path:?}");
+        assert!(path.path.iter().any(|point| point.y < 5.0), "{path:?}");
+    }
+
+    #[test]
+    fn tiles_touching_by_a_corner_are_not_connected() {
+        let navmesh = NavMesh::from_grid(2, 2, 1.0, |x, y| x == y).unwrap();
+
+        assert!(navmesh.is_in_mesh(Vec2::new(0.5, 0.5)));
+        assert!(navmesh.is_in_mesh(Vec2::new(1.5, 1.5)));
+        assert!(navmesh
+            .path(Vec2::new(0.5, 0.5), Vec2::new(1.5, 1.5))
+            .is_none());
+        assert_eq!(
+            NavMesh::from_grid(2, 2, 1.0, |_, _| false).unwrap_err(),
+            NavMeshBuildError::EmptyMesh
+        );
+    }
+
+    #[test]
+    fn cell_size_must_be_positive() {
+        for cell_size in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(
+                NavMesh::from_grid(2, 2, cell_size, |_, _| true).unwrap_err(),
+                NavMeshBuildError::InvalidSetting("cell_size")
+            );
+        }
+    }
+}
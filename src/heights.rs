@@ -0,0 +1,166 @@
+//! Heights of the surface of a [`NavMesh`], to place points back on sloped or uneven terrain
+
+use bevy::prelude::*;
+
+use crate::{spatial::SpatialIndex, NavMesh};
+
+/// Triangles of the source surface, with the height of their vertices above the navmesh plane.
+///
+/// Triangles are kept in a [`SpatialIndex`] to find the one under a point without going through
+/// all of them.
+#[derive(Debug)]
+pub(crate) struct HeightMap {
+    /// Vertices in mesh coordinates, with `z` their height.
+    vertices: Vec<Vec3>,
+    triangles: Vec<[usize; 3]>,
+    index: SpatialIndex,
+}
+
+impl HeightMap {
+    pub(crate) fn new(vertices: Vec<Vec3>, triangles: Vec<[usize; 3]>) -> Self {
+        let bounds = triangles
+            .iter()
+            .map(|triangle| {
+                let points = triangle.map(|vertex| vertices[vertex].xy());
+                (
+                    points[0].min(points[1]).min(points[2]),
+                    points[0].max(points[1]).max(points[2]),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        HeightMap {
+            vertices,
+            triangles,
+            index: SpatialIndex::new(&bounds),
+        }
+    }
+
+    /// Interpolates the height of the surface at `point`.
+    ///
+    /// Points slightly outside of the surface take the height of the closest triangle edge.
+    pub(crate) fn height_at(&self, point: Vec2) -> f32 {
+        let closest = self
+            .index
+            .within(point, point)
+            .filter_map(|triangle| {
+                let [a, b, c] = self.triangles[triangle].map(|vertex| self.vertices[vertex]);
+                let weights = barycentric(point, a.xy(), b.xy(), c.xy())?;
+                Some((weights, Vec3::new(a.z, b.z, c.z)))
+            })
+            .max_by(|(a, _), (b, _)| a.min_element().total_cmp(&b.min_element()));
+
+        match closest {
+            Some((weights, heights)) => {
+                let weights = weights.max(Vec3::ZERO);
+                weights.dot(heights) / weights.dot(Vec3::ONE)
+            }
+            // Far from the surface, use the closest vertex
+            None => self
+                .vertices
+                .iter()
+                .min_by(|a, b| {
+                    a.xy()
+                        .distance_squared(point)
+                        .total_cmp(&b.xy().distance_squared(point))
+                })
+                .map_or(0.0, |vertex| vertex.z),
+        }
+    }
+}
+
+/// Barycentric coordinates of `point` in the triangle `a`, `b`, `c`. All are positive when the
+/// point is inside the triangle. Returns `None` for a degenerate triangle.
+fn barycentric(point: Vec2, a: Vec2, b: Vec2, c: Vec2) -> Option<Vec3> {
+    let area = (b - a).perp_dot(c - a);
+    if area.abs() < f32::EPSILON {
+        return None;
+    }
+    let u = (c - b).perp_dot(point - b) / area;
+    let v = (a - c).perp_dot(point - c) / area;
+    Some(Vec3::new(u, v, 1.0 - u - v))
+}
+
+impl NavMesh {
+    /// Height of the surface at `point`, in mesh coordinates.
+    ///
+    /// Heights are kept when building a [`NavMesh`] from Bevy [`Mesh`]es that are not flat, and
+    /// used to place the points of a [`TransformedPath`](crate::TransformedPath) and of
+    /// [`NavMesh::to_mesh`] on the surface. Flat meshes have a height of `0.0`.
+    pub fn height_at(&self, point: Vec2) -> f32 {
+        self.heights
+            .as_ref()
+            .map_or(0.0, |heights| heights.height_at(point))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn heights_are_interpolated() {
+        let heights = HeightMap::new(
+            vec![
+                Vec3::new(0., 0., 0.),
+                Vec3::new(2., 0., 0.),
+                Vec3::new(2., 2., 1.),
+                Vec3::new(0., 2., 1.),
+                Vec3::new(4., 0., 0.),
+            ],
+            vec![[0, 1, 2], [0, 2, 3], [1, 4, 2]],
+        );
+
+        assert_eq!(heights.height_at(Vec2::new(0., 0.)), 0.);
+        assert_eq!(heights.height_at(Vec2::new(1., 1.)), 0.5);
+        assert_eq!(heights.height_at(Vec2::new(0.5, 1.5)), 0.75);
+        assert_eq!(heights.height_at(Vec2::new(1., 2.)), 1.);
+        assert_eq!(heights.height_at(Vec2::new(3., 0.5)), 0.25);
+        // Outside of the surface
+        assert_eq!(heights.height_at(Vec2::new(1., 2.1)), 1.);
+        assert_eq!(heights.height_at(Vec2::new(10., 10.)), 1.);
+    }
+
+    #[test]
+    fn large_triangles_dont_fill_the_index() {
+        // Small triangles next to one covering a huge plain
+        let mut vertices = vec![
+            Vec3::new(0., 0., 2.),
+            Vec3::new(1e6, 0., 2.),
+            Vec3::new(0., 1e6, 2.),
+        ];
+        let mut triangles = vec![[0, 1, 2]];
+        for i in 0..100 {
+            let x = -1. - i as f32 * 0.01;
+            let first = vertices.len();
+            vertices.extend([
+                Vec3::new(x, 0., 0.),
+                Vec3::new(x, 0.01, 1.),
+                Vec3::new(x - 0.01, 0., 0.),
+            ]);
+            triangles.push([first, first + 1, first + 2]);
+        }
+        let heights = HeightMap::new(vertices, triangles);
+
+        assert_eq!(heights.height_at(Vec2::new(5e5, 1e5)), 2.);
+        assert_eq!(heights.height_at(Vec2::new(-1.5, 0.)), 0.);
+        assert!((heights.height_at(Vec2::new(-1.5, 0.005)) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn degenerate_triangles_are_ignored() {
+        let heights = HeightMap::new(
+            vec![
+                Vec3::new(0., 0., 5.),
+                Vec3::new(1., 1., 5.),
+                Vec3::new(2., 2., 5.),
+                Vec3::new(0., 0., 1.),
+                Vec3::new(2., 0., 1.),
+                Vec3::new(0., 2., 1.),
+            ],
+            vec![[0, 1, 2], [3, 4, 5]],
+        );
+        assert_eq!(heights.height_at(Vec2::new(0.5, 0.5)), 1.);
+        assert_eq!(HeightMap::new(vec![], vec![]).height_at(Vec2::ZERO), 0.);
+    }
+}
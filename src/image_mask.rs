@@ -0,0 +1,204 @@
+//! Build a [`NavMesh`] from an image where the walkable area is painted, for quick prototyping
+
+use bevy::{prelude::*, render::render_resource::TextureFormat};
+use serde::{Deserialize, Serialize};
+
+use crate::{builder::triangulate, grid::grid_area, NavMesh, NavMeshBuildError};
+
+/// Channel of an image compared to [`ImageMaskSettings::threshold`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum MaskChannel {
+    /// The red channel
+    #[default]
+    Red,
+    /// The green channel
+    Green,
+    /// The blue channel
+    Blue,
+    /// The alpha channel, for images painted over a transparent background
+    Alpha,
+}
+
+/// Settings for [`NavMesh::from_image_mask`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ImageMaskSettings {
+    /// The channel read from each pixel. Default is [`MaskChannel::Red`].
+    pub channel: MaskChannel,
+    /// Pixels with a value of the channel at least this one, between `0.0` and `1.0`, are
+    /// walkable. Default is `0.5`.
+    pub threshold: f32,
+    /// Size of a pixel in mesh coordinates. Default is `1.0`.
+    pub scale: f32,
+    /// Minimum area a point should contribute to the contours to be kept, in mesh coordinates.
+    ///
+    /// The contours follow the sides of the pixels. Simplifying them removes the steps of
+    /// diagonal edges. Default is `1.0`.
+    pub simplify: f32,
+}
+
+impl Default for ImageMaskSettings {
+    fn default() -> Self {
+        Self {
+            channel: MaskChannel::Red,
+            threshold: 0.5,
+            scale: 1.0,
+            simplify: 1.0,
+        }
+    }
+}
+
+impl NavMesh {
+    /// Creates a [`NavMesh`] from an image where the walkable area is painted.
+    ///
+    /// Each pixel is walkable if the value of [`ImageMaskSettings::channel`] reaches
+    /// [`ImageMaskSettings::threshold`]. The contours of the walkable area, with its holes, are
+    /// traced then simplified before being triangulated. The bottom left corner of the image is
+    /// at the origin of the mesh, with `y` going up.
+    ///
+    /// Returns [`NavMeshBuildError::InvalidSetting`] if the scale is not a positive number, or if
+    /// the threshold is not between `0.0` and `1.0`.
+    pub fn from_image_mask(
+        image: &Image,
+        settings: &ImageMaskSettings,
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        if !settings.scale.is_finite() || settings.scale <= 0.0 {
+            return Err(NavMeshBuildError::InvalidSetting("scale"));
+        }
+        if !(0.0..=1.0).contains(&settings.threshold) {
+            return Err(NavMeshBuildError::InvalidSetting("threshold"));
+        }
+        let pixels = image.convert(TextureFormat::Rgba8UnormSrgb).ok_or(
+            NavMeshBuildError::UnsupportedImageFormat(image.texture_descriptor.format),
+        )?;
+        let (width, height) = (image.width(), image.height());
+        let channel = settings.channel as usize;
+        let area = grid_area(width, height, settings.scale, |x, y| {
+            // Rows of the image go down
+            let pixel = x as usize + (height - 1 - y) as usize * width as usize;
+            let value = pixels.data[pixel * 4 + channel];
+            value as f32 / 255.0 >= settings.threshold
+        });
+        let mesh = triangulate(&area, settings.simplify)?;
+        Ok(NavMesh::from_polyanya_mesh(mesh))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension},
+    };
+
+    use super::*;
+
+    /// An image of `size` pixels, with the red channel set where `painted` is true
+    fn mask(size: u32, painted: impl Fn(Vec2) -> bool) -> Image {
+        let data = (0..size)
+            .flat_map(|row| (0..size).map(move |column| (column, row)))
+            .flat_map(|(column, row)| {
+                let center = Vec2::new(column as f32 + 0.5, row as f32 + 0.5);
+                let red = if painted(center) { 255 } else { 0 };
+                [red, 0, 0, 255]
+            })
+            .collect();
+        Image::new(
+            Extent3d {
+                width: size,
+                height: size,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+            RenderAssetUsages::default(),
+        )
+    }
+
+    #[test]
+    fn painted_area_with_holes() {
+        // A ring around an island
+        let center = Vec2::splat(20.0);
+        let image = mask(40, |point| {
+            let distance = point.distance(center);
+            (distance < 18.0 && distance > 10.0) || distance < 5.0
+        });
+        let navmesh = NavMesh::from_image_mask(
+            &image,
+            &ImageMaskSettings {
+                scale: 0.5,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        assert_eq!(navmesh.island_count(), 2);
+        assert!(navmesh.is_in_mesh(Vec2::new(10.0, 3.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(10.0, 10.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(3.0, 10.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(10.0, 7.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(10.0, 18.5)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(1.0, 1.0)));
+        assert!(navmesh
+            .path(Vec2::new(3.0, 10.0), Vec2::new(17.0, 10.0))
+            .is_some());
+        assert!(!navmesh.is_reachable(Vec2::new(3.0, 10.0), Vec2::new(10.0, 10.0)));
+    }
+
+    #[test]
+    fn diagonal_edges_are_simplified() {
+        let image = mask(32, |point| point.x > point.y);
+
+        let simplified = NavMesh::from_image_mask(&image, &Default::default()).unwrap();
+        let stairs = NavMesh::from_image_mask(
+            &image,
+            &ImageMaskSettings {
+                simplify: 0.0,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(simplified.get().vertices.len() < 12);
+        assert!(stairs.get().vertices.len() > 60);
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        let image = mask(4, |_| true);
+        for (settings, name) in [
+            (
+                ImageMaskSettings {
+                    scale: 0.0,
+                    ..Default::default()
+                },
+                "scale",
+            ),
+            (
+                ImageMaskSettings {
+                    scale: f32::NAN,
+                    ..Default::default()
+                },
+                "scale",
+            ),
+            (
+                ImageMaskSettings {
+                    threshold: 1.5,
+                    ..Default::default()
+                },
+                "threshold",
+            ),
+            (
+                ImageMaskSettings {
+                    threshold: f32::NAN,
+                    ..Default::default()
+                },
+                "threshold",
+            ),
+        ] {
+            assert_eq!(
+                NavMesh::from_image_mask(&image, &settings).unwrap_err(),
+                NavMeshBuildError::InvalidSetting(name)
+            );
+        }
+    }
+}
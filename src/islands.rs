@@ -0,0 +1,306 @@
+//! Connected parts of a [`NavMesh`], to reject unreachable destinations without a path search
+
+use std::sync::Arc;
+
+use bevy::prelude::*;
+use itertools::Itertools;
+
+use crate::{links::OffMeshLink, NavMesh};
+
+/// Index of the polygon on the other side of the edge from vertex `a` to vertex `b` of
+/// `polygon`, if there is one.
+pub(crate) fn neighbour(mesh: &polyanya::Mesh, polygon: usize, a: u32, b: u32) -> Option<usize> {
+    mesh.vertices[a as usize]
+        .polygons
+        .iter()
+        .filter(|other| **other >= 0 && **other as usize != polygon)
+        .map(|other| *other as usize)
+        .find(|other| mesh.polygons[*other].vertices.contains(&b))
+}
+
+/// Edges of the mesh without a polygon on the other side, as their start and end points.
+pub(crate) fn boundary_edges(mesh: &polyanya::Mesh) -> Vec<(Vec2, Vec2)> {
+    mesh.polygons
+        .iter()
+        .enumerate()
+        .flat_map(|(polygon, vertices)| {
+            vertices
+                .vertices
+                .iter()
+                .circular_tuple_windows()
+                .filter(move |(a, b)| neighbour(mesh, polygon, **a, **b).is_none())
+                .map(|(a, b)| {
+                    (
+                        mesh.vertices[*a as usize].coords,
+                        mesh.vertices[*b as usize].coords,
+                    )
+                })
+        })
+        .collect()
+}
+
+/// Island of each polygon of the mesh. Islands are numbered from `0`, in the order of their
+/// first polygon.
+///
+/// Polyanya also detects islands, but doesn't expose them, and considers polygons sharing only
+/// a vertex connected. This is done once each time the mesh changes.
+pub(crate) fn find_islands(mesh: &polyanya::Mesh) -> Vec<usize> {
+    let mut islands = vec![usize::MAX; mesh.polygons.len()];
+    let mut count = 0;
+    for start in 0..mesh.polygons.len() {
+        if islands[start] != usize::MAX {
+            continue;
+        }
+        islands[start] = count;
+        let mut to_visit = vec![start];
+        while let Some(polygon) = to_visit.pop() {
+            for (a, b) in mesh.polygons[polygon]
+                .vertices
+                .iter()
+                .circular_tuple_windows()
+            {
+                if let Some(next) = neighbour(mesh, polygon, *a, *b) {
+                    if islands[next] == usize::MAX {
+                        islands[next] = count;
+                        to_visit.push(next);
+                    }
+                }
+            }
+        }
+        count += 1;
+    }
+    islands
+}
+
+/// Islands connected by off-mesh links, and which ones can be reached from each other.
+///
+/// Only islands with a link starting or ending on them can lead to another island, so the
+/// reachability is kept between them only. This is done each time the mesh or a link changes.
+#[derive(Debug, Default)]
+pub(crate) struct IslandLinks {
+    /// Index of each island in `reachable`, if a link starts or ends on it
+    linked: Vec<Option<usize>>,
+    /// If each linked island can be reached from each other linked island
+    reachable: Vec<Vec<bool>>,
+}
+
+impl IslandLinks {
+    /// Finds the islands connected by `links`, whose ends are on `link_islands`.
+    pub(crate) fn new(
+        island_count: usize,
+        links: &[OffMeshLink],
+        link_islands: &[[Option<usize>; 2]],
+    ) -> Self {
+        let mut linked = vec![None; island_count];
+        let mut count = 0;
+        // Islands connected by a link, in the direction it can be taken
+        let mut bridges = vec![];
+        for (link, ends) in links.iter().zip(link_islands) {
+            let [Some(start), Some(end)] = *ends else {
+                continue;
+            };
+            for island in [start, end] {
+                if linked[island].is_none() {
+                    linked[island] = Some(count);
+                    count += 1;
+                }
+            }
+            let (start, end) = (linked[start].unwrap(), linked[end].unwrap());
+            bridges.push((start, end));
+            if link.bidirectional {
+                bridges.push((end, start));
+            }
+        }
+        let mut next = vec![vec![]; count];
+        for (start, end) in bridges {
+            next[start].push(end);
+        }
+
+        let reachable = (0..count)
+            .map(|from| {
+                let mut reached = vec![false; count];
+                reached[from] = true;
+                let mut to_visit = vec![from];
+                while let Some(island) = to_visit.pop() {
+                    for next in &next[island] {
+                        if !reached[*next] {
+                            reached[*next] = true;
+                            to_visit.push(*next);
+                        }
+                    }
+                }
+                reached
+            })
+            .collect();
+        IslandLinks { linked, reachable }
+    }
+}
+
+impl NavMesh {
+    /// Updates the islands of the ends of the links, and the islands they connect.
+    pub(crate) fn update_link_islands(&mut self) {
+        self.link_islands = self
+            .links
+            .iter()
+            .map(|link| self.link_ends_islands(link))
+            .collect();
+        self.connect_islands();
+    }
+
+    /// Updates the islands connected by the links, see [`IslandLinks`].
+    pub(crate) fn connect_islands(&mut self) {
+        self.island_links = Arc::new(IslandLinks::new(
+            self.island_count(),
+            &self.links,
+            &self.link_islands,
+        ));
+    }
+
+    /// Number of islands of the mesh, parts that are not connected to each other by polygon
+    /// edges.
+    ///
+    /// More than one island can be a sign of an unintentionally isolated region.
+    pub fn island_count(&self) -> usize {
+        self.islands.iter().max().map_or(0, |last| last + 1)
+    }
+
+    /// The island `point` is on, see [`NavMesh::island_count`].
+    pub fn island_of(&self, point: Vec2) -> Option<usize> {
+        self.polygon_at(point).map(|polygon| self.islands[polygon])
+    }
+
+    /// The island a 3d point is on, using the [`NavMesh::transform`].
+    pub fn transformed_island_of(&self, point: Vec3) -> Option<usize> {
+        self.island_of(self.transform.transform_point(point).xy())
+    }
+
+    /// Checks if there is a path from `from` to `to`, without searching for it.
+    ///
+    /// Points are reachable when they are on the same island, or when off-mesh links lead from
+    /// the island of `from` to the island of `to`. Area types are ignored.
+    ///
+    /// The polygons under the points are found in a spatial index, and the islands that can be
+    /// reached from each other are kept when a link changes, so this doesn't go through the
+    /// whole mesh or the links.
+    pub fn is_reachable(&self, from: Vec2, to: Vec2) -> bool {
+        let (Some(from), Some(to)) = (self.island_of(from), self.island_of(to)) else {
+            return false;
+        };
+        self.is_island_reachable(from, to)
+    }
+
+    /// Checks if off-mesh links lead from the island `from` to the island `to`.
+    pub(crate) fn is_island_reachable(&self, from: usize, to: usize) -> bool {
+        if from == to {
+            return true;
+        }
+        let linked = &self.island_links.linked;
+        match (linked.get(from), linked.get(to)) {
+            (Some(Some(from)), Some(Some(to))) => self.island_links.reachable[*from][*to],
+            _ => false,
+        }
+    }
+
+    /// Checks if there is a path between two 3d points, without searching for it, using the
+    /// [`NavMesh::transform`].
+    ///
+    /// See [`NavMesh::is_reachable`].
+    pub fn transformed_is_reachable(&self, from: Vec3, to: Vec3) -> bool {
+        self.is_reachable(
+            self.transform.transform_point(from).xy(),
+            self.transform.transform_point(to).xy(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::links::OffMeshLink;
+
+    /// Three squares, the first two sharing an edge
+    fn three_rooms() -> NavMesh {
+        NavMesh::from_polyanya_mesh(
+            polyanya::Trimesh {
+                vertices: vec![
+                    Vec2::new(0., 0.),
+                    Vec2::new(2., 0.),
+                    Vec2::new(2., 2.),
+                    Vec2::new(0., 2.),
+                    Vec2::new(4., 0.),
+                    Vec2::new(4., 2.),
+                    Vec2::new(5., 0.),
+                    Vec2::new(7., 0.),
+                    Vec2::new(7., 2.),
+                    Vec2::new(5., 2.),
+                ],
+                triangles: vec![
+                    [0, 1, 2],
+                    [0, 2, 3],
+                    [1, 4, 5],
+                    [1, 5, 2],
+                    [6, 7, 8],
+                    [6, 8, 9],
+                ],
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn islands_are_detected() {
+        let navmesh = three_rooms();
+
+        assert_eq!(navmesh.island_count(), 2);
+        assert_eq!(navmesh.island_of(Vec2::new(1., 1.)), Some(0));
+        assert_eq!(navmesh.island_of(Vec2::new(3., 1.)), Some(0));
+        assert_eq!(navmesh.island_of(Vec2::new(6., 1.)), Some(1));
+        assert_eq!(navmesh.island_of(Vec2::new(4.5, 1.)), None);
+
+        assert!(navmesh.is_reachable(Vec2::new(1., 1.), Vec2::new(3., 1.)));
+        assert!(!navmesh.is_reachable(Vec2::new(1., 1.), Vec2::new(6., 1.)));
+        assert!(!navmesh.is_reachable(Vec2::new(1., 1.), Vec2::new(4.5, 1.)));
+    }
+
+    #[test]
+    fn links_connect_islands() {
+        let mut navmesh = three_rooms();
+        let (from, to) = (Vec2::new(1., 1.), Vec2::new(6., 1.));
+
+        navmesh.add_link(OffMeshLink {
+            start: Vec2::new(3.5, 1.),
+            end: Vec2::new(5.5, 1.),
+            bidirectional: false,
+            cost: 1.,
+            tag: 0,
+        });
+        assert!(navmesh.is_reachable(from, to));
+        assert!(!navmesh.is_reachable(to, from));
+        assert_eq!(
+            navmesh.path(from, to).is_some(),
+            navmesh.is_reachable(from, to)
+        );
+        assert_eq!(
+            navmesh.path(to, from).is_some(),
+            navmesh.is_reachable(to, from)
+        );
+
+        // Changing the link updates the islands it connects
+        navmesh.set_link(
+            0,
+            OffMeshLink {
+                bidirectional: true,
+                ..navmesh.links()[0]
+            },
+        );
+        assert!(navmesh.is_reachable(to, from));
+        navmesh.set_link(
+            0,
+            OffMeshLink {
+                end: Vec2::new(3., 0.5),
+                ..navmesh.links()[0]
+            },
+        );
+        assert!(!navmesh.is_reachable(from, to));
+    }
+}
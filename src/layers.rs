@@ -0,0 +1,346 @@
+//! Layered navigation meshes, for walkable areas overlapping each other like bridges or floors
+
+use std::sync::{Arc, RwLock};
+
+use bevy::{prelude::*, reflect::TypePath, utils::HashMap};
+use itertools::Itertools;
+
+use crate::{
+    areas::NavQueryFilter,
+    links::{search_links, GraphLink},
+    NavMesh, TransformedPath,
+};
+
+/// A connection between two layers of a [`LayeredNavMesh`], like stairs or an elevator.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LayerConnection {
+    /// Where the connection starts, in world coordinates.
+    pub start: Vec3,
+    /// Layer of the start of the connection.
+    pub start_layer: usize,
+    /// Where the connection ends, in world coordinates.
+    pub end: Vec3,
+    /// Layer of the end of the connection.
+    pub end_layer: usize,
+    /// If the connection can also be taken from `end` to `start`.
+    pub bidirectional: bool,
+    /// Cost of taking the connection, compared to the length of paths on the layers.
+    pub cost: f32,
+    /// Tag to identify the kind of connection, for example to play the matching animation.
+    pub tag: u32,
+}
+
+/// Several [`NavMesh`] layers, each one being flat enough to be projected in 2D, with
+/// connections between them.
+///
+/// This can represent walkable areas that overlap once projected, like multi-story buildings,
+/// bridges or overpasses.
+#[derive(Debug, TypePath, Clone, Asset, Default)]
+pub struct LayeredNavMesh {
+    layers: Vec<NavMesh>,
+    connections: Vec<LayerConnection>,
+    /// Paths between the ends of the connections, searched when needed
+    walks: LayerWalks,
+}
+
+/// Paths on the layers between the ends of the connections, by index of the ends (the start then
+/// the end of each connection), with their cost. They are searched when a query first needs them,
+/// and kept for all the queries without the same area types.
+type ConnectionWalks = HashMap<(usize, usize), Option<(Vec<Vec3>, f32)>>;
+
+/// Walks between connections, by sorted excluded area types
+type LayerWalks = Arc<RwLock<HashMap<Vec<u8>, ConnectionWalks>>>;
+
+impl LayeredNavMesh {
+    /// Creates a [`LayeredNavMesh`] from its layers.
+    pub fn new(layers: Vec<NavMesh>) -> Self {
+        Self {
+            layers,
+            ..default()
+        }
+    }
+
+    /// Adds a connection between two layers, returning its index.
+    ///
+    /// # Panics
+    ///
+    /// Panics if one of the layers of the connection doesn't exist.
+    pub fn add_connection(&mut self, connection: LayerConnection) -> usize {
+        for layer in [connection.start_layer, connection.end_layer] {
+            assert!(
+                layer < self.layers.len(),
+                "connection to layer {layer}, but there are only {} layers",
+                self.layers.len()
+            );
+        }
+        // Walks between the other connections are still valid, but clones of this mesh share the
+        // cache
+        let walks = self.walks.read().unwrap().clone();
+        self.walks = Arc::new(RwLock::new(walks));
+        self.connections.push(connection);
+        self.connections.len() - 1
+    }
+
+    /// The layers of this mesh.
+    pub fn layers(&self) -> &[NavMesh] {
+        &self.layers
+    }
+
+    /// The connections between layers.
+    pub fn connections(&self) -> &[LayerConnection] {
+        &self.connections
+    }
+
+    /// The layer a 3d point is on: among the layers containing the point once projected, the one
+    /// with its surface closest to the point.
+    pub fn layer_at(&self, point: Vec3) -> Option<usize> {
+        self.layers
+            .iter()
+            .enumerate()
+            .filter(|(_, layer)| layer.transformed_is_in_mesh(point))
+            .map(|(index, layer)| {
+                let projected = layer.transform.transform_point(point).xy();
+                let surface = layer
+                    .inverse_transform()
+                    .transform_point3(projected.extend(layer.height_at(projected)));
+                (index, surface.distance_squared(point))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(index, _)| index)
+    }
+
+    /// Get a path between two points, picking the layer of each point with
+    /// [`LayeredNavMesh::layer_at`].
+    ///
+    /// [`TransformedPath::links`] lists the connections between layers taken by the path, by
+    /// their index in [`LayeredNavMesh::connections`].
+    pub fn transformed_path(&self, from: Vec3, to: Vec3) -> Option<TransformedPath> {
+        self.transformed_filtered_path(from, to, &NavQueryFilter::default())
+    }
+
+    /// Get a path between two points, not going through the area types excluded by `filter` on
+    /// any layer.
+    ///
+    /// Paths avoid costly areas, see [`NavMesh::set_area_cost`].
+    pub fn transformed_filtered_path(
+        &self,
+        from: Vec3,
+        to: Vec3,
+        filter: &NavQueryFilter,
+    ) -> Option<TransformedPath> {
+        self.layered_path(from, to, &filter.excluded)
+    }
+
+    /// Finds the cheapest path between two points, through the connections between layers.
+    ///
+    /// Nodes of the search are the start, the destination and the ends of the connections, see
+    /// [`search_links`]. Paths between the ends of the connections are searched once, see
+    /// [`LayeredNavMesh::walk_between_ends`].
+    fn layered_path(&self, from: Vec3, to: Vec3, excluded: &[u8]) -> Option<TransformedPath> {
+        let from_layer = self.layer_at(from)?;
+        let to_layer = self.layer_at(to)?;
+
+        let nodes = [(from, from_layer), (to, to_layer)]
+            .into_iter()
+            .chain(self.connections.iter().flat_map(|connection| {
+                [
+                    (connection.start, connection.start_layer),
+                    (connection.end, connection.end_layer),
+                ]
+            }))
+            .collect::<Vec<_>>();
+        let points = nodes.iter().map(|(point, _)| *point).collect::<Vec<_>>();
+        let connections = self
+            .connections
+            .iter()
+            .map(|connection| GraphLink {
+                cost: connection.cost,
+                bidirectional: connection.bidirectional,
+                tag: connection.tag,
+            })
+            .collect::<Vec<_>>();
+        let walk = |current: usize, next: usize| {
+            let (point, layer) = nodes[current];
+            if nodes[next].1 != layer {
+                return None;
+            }
+            if current >= 2 && next >= 2 {
+                return self.walk_between_ends(current - 2, next - 2, excluded);
+            }
+            let (path, cost) =
+                self.layers[layer].transformed_costed_path(point, nodes[next].0, excluded)?;
+            Some((path.path, cost))
+        };
+        let (path, links, _) = search_links(&points, &connections, walk)?;
+        Some(TransformedPath {
+            length: std::iter::once(from)
+                .chain(path.iter().copied())
+                .tuple_windows()
+                .map(|(a, b)| a.distance(b))
+                .sum(),
+            path,
+            links,
+        })
+    }
+
+    /// The path on a layer between two ends of the connections, by index of the ends (the start
+    /// then the end of each connection), without the given area types, with its cost.
+    ///
+    /// It is searched the first time it's needed. `excluded` must be sorted.
+    fn walk_between_ends(
+        &self,
+        from: usize,
+        to: usize,
+        excluded: &[u8],
+    ) -> Option<(Vec<Vec3>, f32)> {
+        if let Some(walk) = self
+            .walks
+            .read()
+            .unwrap()
+            .get(excluded)
+            .and_then(|walks| walks.get(&(from, to)))
+        {
+            return walk.clone();
+        }
+
+        let [(start, layer), (end, end_layer)] = [from, to].map(|end| {
+            let connection = &self.connections[end / 2];
+            if end % 2 == 0 {
+                (connection.start, connection.start_layer)
+            } else {
+                (connection.end, connection.end_layer)
+            }
+        });
+        if layer != end_layer {
+            return None;
+        }
+        let walk = self.layers[layer]
+            .transformed_costed_path(start, end, excluded)
+            .map(|(path, cost)| (path.path, cost));
+        self.walks
+            .write()
+            .unwrap()
+            .entry(excluded.to_vec())
+            .or_default()
+            .insert((from, to), walk.clone());
+        walk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::{
+        mesh::Indices, render_asset::RenderAssetUsages, render_resource::PrimitiveTopology,
+    };
+
+    use super::*;
+    use crate::{areas::DEFAULT_AREA, links::TakenLink};
+
+    const STAIRS: u32 = 3;
+
+    /// A horizontal rectangle at height `y`
+    fn floor(min: Vec2, max: Vec2, y: f32) -> NavMesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [min.x, y, -min.y],
+                [max.x, y, -min.y],
+                [max.x, y, -max.y],
+                [min.x, y, -max.y],
+            ],
+        );
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 4]);
+        mesh.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+        NavMesh::from_bevy_mesh(&mesh)
+    }
+
+    /// The ground, with a bridge above it reachable by stairs
+    fn bridge() -> LayeredNavMesh {
+        let mut layered = LayeredNavMesh::new(vec![
+            floor(Vec2::ZERO, Vec2::splat(10.0), 0.0),
+            floor(Vec2::new(4.0, 0.0), Vec2::new(6.0, 10.0), 3.0),
+        ]);
+        layered.add_connection(LayerConnection {
+            start: Vec3::new(3.0, 0.0, -1.0),
+            start_layer: 0,
+            end: Vec3::new(5.0, 3.0, -1.0),
+            end_layer: 1,
+            bidirectional: true,
+            cost: 4.0,
+            tag: STAIRS,
+        });
+        layered
+    }
+
+    #[test]
+    fn layer_is_picked_by_height() {
+        let layered = bridge();
+        assert_eq!(layered.layer_at(Vec3::new(5.0, 0.0, -5.0)), Some(0));
+        assert_eq!(layered.layer_at(Vec3::new(5.0, 2.9, -5.0)), Some(1));
+        assert_eq!(layered.layer_at(Vec3::new(1.0, 2.9, -5.0)), Some(0));
+        assert_eq!(layered.layer_at(Vec3::new(20.0, 0.0, -5.0)), None);
+    }
+
+    #[test]
+    fn path_takes_connection_between_layers() {
+        let layered = bridge();
+
+        let path = layered
+            .transformed_path(Vec3::new(1.0, 0.0, -1.0), Vec3::new(5.0, 3.0, -5.0))
+            .unwrap();
+        assert_eq!(path.path.len(), 3);
+        assert!(path.path[0].distance(Vec3::new(3.0, 0.0, -1.0)) < 1e-5);
+        assert!(path.path[2].distance(Vec3::new(5.0, 3.0, -5.0)) < 1e-5);
+        assert_eq!(
+            path.links,
+            vec![TakenLink {
+                waypoint: 1,
+                link: 0,
+                tag: STAIRS
+            }]
+        );
+
+        // Under the bridge, the path stays on the ground
+        let path = layered
+            .transformed_path(Vec3::new(1.0, 0.0, -1.0), Vec3::new(5.0, 0.0, -5.0))
+            .unwrap();
+        assert!(path.links.is_empty());
+        assert!(path.path.iter().all(|point| point.y.abs() < 1e-5));
+    }
+
+    #[test]
+    fn walks_between_connections_are_cached() {
+        let mut layered = bridge();
+        layered.add_connection(LayerConnection {
+            start: Vec3::new(3.0, 0.0, -9.0),
+            end: Vec3::new(5.0, 3.0, -9.0),
+            ..layered.connections()[0]
+        });
+        let from = Vec3::new(1.0, 0.0, -1.0);
+        let to = Vec3::new(5.0, 3.0, -5.0);
+        assert!(layered.transformed_path(from, to).is_some());
+
+        // Only the walks needed by the search, between ends on the same layer
+        let cached = layered.walks.read().unwrap()[&[][..]]
+            .keys()
+            .copied()
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(cached, vec![(0, 2), (1, 3)]);
+        let nowhere = NavQueryFilter::default().exclude(DEFAULT_AREA);
+        assert!(layered
+            .transformed_filtered_path(from, to, &nowhere)
+            .is_none());
+    }
+
+    #[test]
+    #[should_panic]
+    fn connection_to_missing_layer_is_rejected() {
+        let mut layered = bridge();
+        layered.add_connection(LayerConnection {
+            end_layer: 2,
+            ..layered.connections()[0]
+        });
+    }
+}
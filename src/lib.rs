@@ -11,57 +11,198 @@
     missing_docs
 )]
 
-use std::sync::Arc;
+use std::{
+    error::Error,
+    fmt::Display,
+    sync::{Arc, RwLock},
+};
 
-use bevy::math::Vec3Swizzles;
+use bevy::math::{Affine3A, I64Vec3, Vec3Swizzles};
 use bevy::reflect::TypePath;
-use bevy::render::mesh::{MeshVertexAttributeId, VertexAttributeValues};
+use bevy::render::mesh::{MeshVertexAttribute, VertexAttributeValues};
 use bevy::render::render_asset::RenderAssetUsages;
+use bevy::utils::{HashMap, HashSet};
 use bevy::{
     prelude::*,
-    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    render::{
+        mesh::Indices,
+        render_resource::{PrimitiveTopology, TextureFormat},
+    },
 };
+use geo::MultiPolygon;
 use itertools::Itertools;
 
+pub mod agent;
+pub mod areas;
 pub mod asset_loaders;
+pub mod bake;
+pub mod builder;
+mod grid;
+mod heights;
+pub mod image_mask;
+mod islands;
+pub mod layers;
+pub mod links;
+pub mod obstacles;
+pub mod pathfinding;
+mod random;
+pub mod raycast;
+mod spatial;
+#[cfg(feature = "svg")]
+pub mod svg;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(feature = "tiled")]
+pub mod tiled;
 
 /// Bevy plugin to add support for the [`NavMesh`] asset type.
+///
+/// It also resolves [`PathRequest`](pathfinding::PathRequest) components in the background, as
+/// set by the [`PathSearchMode`](pathfinding::PathSearchMode) resource, and moves
+/// [`NavAgent`](agent::NavAgent) entities along their computed paths.
 #[derive(Debug, Clone, Copy)]
 pub struct VleueNavigatorPlugin;
 
 impl Plugin for VleueNavigatorPlugin {
     fn build(&self, app: &mut App) {
         app.register_asset_loader(asset_loaders::NavMeshPolyanyaLoader)
-            .init_asset::<NavMesh>();
+            .init_asset::<NavMesh>()
+            .init_asset::<layers::LayeredNavMesh>();
+        #[cfg(feature = "gltf")]
+        app.register_asset_loader(asset_loaders::NavMeshGltfLoader);
+        #[cfg(feature = "png")]
+        app.register_asset_loader(asset_loaders::NavMeshImageMaskLoader);
+        #[cfg(feature = "tiled")]
+        app.register_asset_loader(asset_loaders::NavMeshTiledLoader);
+        #[cfg(feature = "svg")]
+        app.register_asset_loader(asset_loaders::NavMeshSvgLoader);
+        app.init_resource::<pathfinding::PathSearchMode>()
+            .add_event::<agent::AgentArrived>()
+            .add_event::<agent::AgentStuck>()
+            .add_systems(
+                Update,
+                (
+                    pathfinding::compute_paths,
+                    pathfinding::poll_path_tasks,
+                    agent::move_agents,
+                )
+                    .chain(),
+            )
+            .add_systems(
+                PostUpdate,
+                obstacles::cut_obstacles
+                    .after(bevy::transform::TransformSystem::TransformPropagate),
+            );
     }
 }
 
 /// A path between two points, in 3 dimensions using [`NavMesh::transform`].
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct TransformedPath {
     /// Length of the path.
     pub length: f32,
     /// Coordinates for each step of the path. The destination is the last step.
     pub path: Vec<Vec3>,
+    /// Off-mesh links taken by the path, in order.
+    pub links: Vec<links::TakenLink>,
 }
 
 pub use polyanya::Path;
 use polyanya::Trimesh;
 
+/// Vertices closer than this distance are welded together when building a [`NavMesh`] from several meshes.
+const WELDING_DISTANCE: f32 = 1e-4;
+
+/// Meshes built from a [`NavMesh`] without some area types, by sorted list of excluded area types.
+type FilteredMeshes = Arc<RwLock<HashMap<Vec<u8>, Option<Arc<polyanya::Mesh>>>>>;
+
+/// Meshes split along the area types of a [`NavMesh`], by sorted list of excluded area types.
+type CostedMeshes = Arc<RwLock<HashMap<Vec<u8>, Option<Arc<areas::CostedMesh>>>>>;
+
+/// Paths between the ends of the off-mesh links of a [`NavMesh`], by sorted list of excluded
+/// area types.
+type LinkWalks = Arc<RwLock<HashMap<Vec<u8>, links::Walks>>>;
+
+/// Settings to build a [`NavMesh`] from Bevy [`Mesh`]es, see
+/// [`NavMesh::try_from_bevy_meshes_with_settings`].
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FromMeshSettings {
+    /// Up axis of the meshes, in world coordinates. The meshes are projected on the plane
+    /// perpendicular to this axis.
+    ///
+    /// Defaults to the average of the normals of the triangles, weighted by their area.
+    pub up: Option<Vec3>,
+    /// Maximum angle in radians between the up axis and the normal of a walkable triangle.
+    /// Steeper triangles are removed.
+    ///
+    /// By default, only vertical triangles are removed.
+    pub max_slope: Option<f32>,
+}
+
 /// A navigation mesh
 #[derive(Debug, TypePath, Clone, Asset)]
 pub struct NavMesh {
     mesh: Arc<polyanya::Mesh>,
     transform: Transform,
+    /// The mesh before obstacles were cut out of it
+    uncut: Option<obstacles::Uncut>,
+    /// Clearance kept from the edges of the mesh
+    agent_radius: f32,
+    /// Regions of each area type, in mesh coordinates
+    areas: Arc<HashMap<u8, MultiPolygon<f32>>>,
+    /// Cost multiplier of each area type
+    area_costs: HashMap<u8, f32>,
+    /// Meshes without some area types, built when needed
+    filtered: FilteredMeshes,
+    /// Meshes with a single area type per polygon, built when needed
+    costed: CostedMeshes,
+    /// Off-mesh links between points of the mesh
+    links: Vec<links::OffMeshLink>,
+    /// Paths between the ends of the links, searched when needed
+    walks: LinkWalks,
+    /// Heights of the source surface, for meshes that are not flat
+    heights: Option<Arc<heights::HeightMap>>,
+    /// Island of each polygon of the mesh
+    islands: Arc<Vec<usize>>,
+    /// Polygons of the mesh by their bounding box
+    polygon_index: Arc<spatial::SpatialIndex>,
+    /// Edges of the mesh without a polygon on the other side
+    boundary: Arc<Vec<(Vec2, Vec2)>>,
+    /// Edges of the boundary by their bounding box
+    boundary_index: Arc<spatial::SpatialIndex>,
+    /// Triangles of the mesh with their area, to pick random points
+    area_triangles: Arc<random::AreaTriangles>,
+    /// Island of the start and of the end of each link
+    link_islands: Vec<[Option<usize>; 2]>,
+    /// Islands connected by the links
+    island_links: Arc<islands::IslandLinks>,
 }
 
 impl NavMesh {
     /// Builds a [`NavMesh`] from a Polyanya [`Mesh`](polyanya::Mesh)
     pub fn from_polyanya_mesh(mesh: polyanya::Mesh) -> NavMesh {
-        NavMesh {
-            mesh: Arc::new(mesh),
+        let mut navmesh = NavMesh {
+            mesh: Default::default(),
             transform: Transform::IDENTITY,
-        }
+            uncut: None,
+            agent_radius: 0.0,
+            areas: Default::default(),
+            area_costs: Default::default(),
+            filtered: Default::default(),
+            costed: Default::default(),
+            links: vec![],
+            walks: Default::default(),
+            heights: None,
+            islands: Default::default(),
+            polygon_index: Default::default(),
+            boundary: Default::default(),
+            boundary_index: Default::default(),
+            area_triangles: Default::default(),
+            link_islands: vec![],
+            island_links: Default::default(),
+        };
+        navmesh.set_mesh(Arc::new(mesh));
+        navmesh
     }
 
     /// Creates a [`NavMesh`] from a Bevy [`Mesh`], assuming it constructs a 2D structure.
@@ -69,41 +210,246 @@ impl NavMesh {
     /// The [`polyanya::Mesh`] generated in the process can be modified via `callback`.
     ///
     /// Only supports meshes with the [`PrimitiveTopology::TriangleList`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Mesh`] can't be converted, see [`NavMesh::try_from_bevy_mesh_and_then`]
+    /// for a fallible version.
     pub fn from_bevy_mesh_and_then(mesh: &Mesh, callback: impl Fn(&mut polyanya::Mesh)) -> NavMesh {
-        let normal = get_vectors(mesh, Mesh::ATTRIBUTE_NORMAL).next().unwrap();
-        let rotation = Quat::from_rotation_arc(normal, Vec3::Z);
+        Self::try_from_bevy_mesh_and_then(mesh, callback)
+            .unwrap_or_else(|error| panic!("Invalid mesh for a NavMesh: {error}"))
+    }
 
-        let vertices = get_vectors(mesh, Mesh::ATTRIBUTE_POSITION)
-            .map(|vertex| rotation.mul_vec3(vertex))
-            .map(|coords| coords.xy())
-            .collect();
+    /// Creates a [`NavMesh`] from a Bevy [`Mesh`], assuming it constructs a 2D structure.
+    /// All triangle normals are aligned during the conversion, so the orientation of the [`Mesh`] does not matter.
+    ///
+    /// Only supports meshes with the [`PrimitiveTopology::TriangleList`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Mesh`] can't be converted, see [`NavMesh::try_from_bevy_mesh`]
+    /// for a fallible version.
+    pub fn from_bevy_mesh(mesh: &Mesh) -> NavMesh {
+        Self::from_bevy_mesh_and_then(mesh, |_| {})
+    }
+
+    /// Tries to create a [`NavMesh`] from a Bevy [`Mesh`], assuming it constructs a 2D structure.
+    /// All triangle normals are aligned during the conversion, so the orientation of the [`Mesh`] does not matter.
+    /// The [`polyanya::Mesh`] generated in the process can be modified via `callback`.
+    ///
+    /// Returns an error if the [`Mesh`] doesn't use the [`PrimitiveTopology::TriangleList`], or doesn't
+    /// have the indices or positions needed.
+    pub fn try_from_bevy_mesh_and_then(
+        mesh: &Mesh,
+        callback: impl Fn(&mut polyanya::Mesh),
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        Self::try_from_bevy_meshes_and_then(&[(mesh, GlobalTransform::IDENTITY)], callback)
+    }
+
+    /// Tries to create a [`NavMesh`] from a Bevy [`Mesh`], assuming it constructs a 2D structure.
+    /// All triangle normals are aligned during the conversion, so the orientation of the [`Mesh`] does not matter.
+    ///
+    /// Returns an error if the [`Mesh`] doesn't use the [`PrimitiveTopology::TriangleList`], or doesn't
+    /// have the indices or positions needed.
+    pub fn try_from_bevy_mesh(mesh: &Mesh) -> Result<NavMesh, NavMeshBuildError> {
+        Self::try_from_bevy_mesh_and_then(mesh, |_| {})
+    }
+
+    /// Creates a [`NavMesh`] from several Bevy [`Mesh`]es placed in the world by their [`GlobalTransform`],
+    /// assuming they construct a 2D structure together.
+    ///
+    /// Vertices at the same position in different meshes or primitives are welded together, so that
+    /// the meshes are connected in the resulting [`NavMesh`]. The orientation of the [`NavMesh`] is
+    /// determined by the average normal of the triangles, see [`FromMeshSettings`].
+    ///
+    /// Only supports meshes with the [`PrimitiveTopology::TriangleList`].
+    ///
+    /// # Panics
+    ///
+    /// Panics if a [`Mesh`] can't be converted, see [`NavMesh::try_from_bevy_meshes`]
+    /// for a fallible version.
+    pub fn from_bevy_meshes(meshes: &[(&Mesh, GlobalTransform)]) -> NavMesh {
+        Self::try_from_bevy_meshes_and_then(meshes, |_| {})
+            .unwrap_or_else(|error| panic!("Invalid mesh for a NavMesh: {error}"))
+    }
 
-        let triangles = mesh
-            .indices()
-            .expect("No polygon indices found in mesh")
+    /// Tries to create a [`NavMesh`] from several Bevy [`Mesh`]es placed in the world by their
+    /// [`GlobalTransform`], assuming they construct a 2D structure together.
+    ///
+    /// See [`NavMesh::from_bevy_meshes`].
+    pub fn try_from_bevy_meshes(
+        meshes: &[(&Mesh, GlobalTransform)],
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        Self::try_from_bevy_meshes_and_then(meshes, |_| {})
+    }
+
+    /// Tries to create a [`NavMesh`] from several Bevy [`Mesh`]es placed in the world by their
+    /// [`GlobalTransform`], assuming they construct a 2D structure together.
+    /// The [`polyanya::Mesh`] generated in the process can be modified via `callback`.
+    ///
+    /// See [`NavMesh::from_bevy_meshes`].
+    pub fn try_from_bevy_meshes_and_then(
+        meshes: &[(&Mesh, GlobalTransform)],
+        callback: impl Fn(&mut polyanya::Mesh),
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        Self::try_from_bevy_meshes_with_settings(meshes, &FromMeshSettings::default(), callback)
+    }
+
+    /// Tries to create a [`NavMesh`] from several Bevy [`Mesh`]es placed in the world by their
+    /// [`GlobalTransform`], choosing the up axis and the walkable triangles with `settings`.
+    /// The [`polyanya::Mesh`] generated in the process can be modified via `callback`.
+    ///
+    /// See [`NavMesh::from_bevy_meshes`].
+    pub fn try_from_bevy_meshes_with_settings(
+        meshes: &[(&Mesh, GlobalTransform)],
+        settings: &FromMeshSettings,
+        callback: impl Fn(&mut polyanya::Mesh),
+    ) -> Result<NavMesh, NavMeshBuildError> {
+        let mut welded = HashMap::new();
+        let mut positions = vec![];
+        let mut triangles = HashSet::new();
+        let mut ordered_triangles = vec![];
+        let mut triangle_areas = vec![];
+
+        for (mesh, transform) in meshes {
+            if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+                return Err(NavMeshBuildError::UnsupportedTopology(
+                    mesh.primitive_topology(),
+                ));
+            }
+            let affine = transform.affine();
+
+            let vertices = get_vectors(mesh, Mesh::ATTRIBUTE_POSITION)?
+                .map(|vertex| affine.transform_point3(vertex))
+                .map(|vertex| weld(&mut welded, &mut positions, vertex))
+                .collect::<Vec<_>>();
+
+            let vertex_areas = areas::get_areas(mesh)?;
+            let indices = mesh.indices().ok_or(NavMeshBuildError::MissingIndices)?;
+            if let Some(index) = indices.iter().find(|index| *index >= vertices.len()) {
+                return Err(NavMeshBuildError::IndexOutOfBounds(index));
+            }
+            // A mirroring transform reverses the winding order of the triangles
+            let mirrored = affine.matrix3.determinant() < 0.0;
+            for (a, b, c) in indices.iter().tuples::<(_, _, _)>() {
+                let area = vertex_areas.as_ref().map_or(areas::DEFAULT_AREA, |areas| {
+                    let area =
+                        |vertex: usize| areas.get(vertex).copied().unwrap_or(areas::DEFAULT_AREA);
+                    // The area type of most vertices, or of the first one if they all differ
+                    if area(b) == area(c) {
+                        area(b)
+                    } else {
+                        area(a)
+                    }
+                });
+                let triangle = if mirrored {
+                    [vertices[a], vertices[c], vertices[b]]
+                } else {
+                    [vertices[a], vertices[b], vertices[c]]
+                };
+                let [a, b, c] = triangle;
+                if a == b || b == c || c == a {
+                    continue;
+                }
+                let mut sorted = triangle;
+                sorted.sort_unstable();
+                if triangles.insert(sorted) {
+                    ordered_triangles.push(triangle);
+                    triangle_areas.push(area);
+                }
+            }
+        }
+
+        // Not normalized, so that larger triangles weigh more in the average
+        let face_normal = |[a, b, c]: [usize; 3]| {
+            (positions[b] - positions[a]).cross(positions[c] - positions[a])
+        };
+        let up = settings
+            .up
+            .unwrap_or_else(|| ordered_triangles.iter().copied().map(face_normal).sum())
+            .try_normalize()
+            .ok_or(NavMeshBuildError::EmptyMesh)?;
+        let min_cos = settings.max_slope.map(f32::cos);
+
+        let mut walkable = vec![];
+        for (triangle, area) in ordered_triangles.into_iter().zip(triangle_areas) {
+            let cos = face_normal(triangle).normalize_or_zero().dot(up);
+            // Vertical triangles would be flattened to a line
+            if cos.abs() < 1e-6 || min_cos.is_some_and(|min_cos| cos < min_cos) {
+                continue;
+            }
+            let [a, b, c] = triangle;
+            // Triangles facing down are flipped to be counter clockwise once projected
+            walkable.push((if cos < 0.0 { [a, c, b] } else { [a, b, c] }, area));
+        }
+        if walkable.is_empty() {
+            return Err(NavMeshBuildError::EmptyMesh);
+        }
+        // Remove the vertices of the triangles that were removed
+        let mut used = vec![false; positions.len()];
+        for (triangle, _) in &walkable {
+            triangle.iter().for_each(|vertex| used[*vertex] = true);
+        }
+        let remapped = used
             .iter()
-            .tuples::<(_, _, _)>()
-            .map(|(a, b, c)| [a, b, c])
-            .collect();
+            .scan(0, |next, used| {
+                *next += *used as usize;
+                Some(*next - 1)
+            })
+            .collect::<Vec<_>>();
+        let used_positions = positions
+            .into_iter()
+            .zip(used)
+            .filter_map(|(position, used)| used.then_some(position))
+            .collect::<Vec<_>>();
+        let walkable = walkable
+            .into_iter()
+            .map(|(triangle, area)| (triangle.map(|vertex| remapped[vertex]), area));
+        let (ordered_triangles, triangle_areas): (Vec<_>, Vec<_>) = walkable.unzip();
 
+        let rotation = Quat::from_rotation_arc(up, Vec3::Z);
+        let positions = used_positions
+            .into_iter()
+            .map(|vertex| rotation.mul_vec3(vertex))
+            .collect::<Vec<_>>();
+        let vertices = positions
+            .iter()
+            .map(|vertex| vertex.xy())
+            .collect::<Vec<_>>();
+        let mut regions = HashMap::<u8, Vec<_>>::new();
+        for (triangle, area) in ordered_triangles.iter().zip(triangle_areas) {
+            if area != areas::DEFAULT_AREA {
+                regions.entry(area).or_default().push(builder::to_polygon(
+                    &triangle.map(|vertex| vertices[vertex]),
+                ));
+            }
+        }
+        let heights = positions
+            .iter()
+            .any(|vertex| vertex.z.abs() > WELDING_DISTANCE)
+            .then(|| {
+                Arc::new(heights::HeightMap::new(
+                    positions,
+                    ordered_triangles.clone(),
+                ))
+            });
         let mut polyanya_mesh = Trimesh {
             vertices,
-            triangles,
+            triangles: ordered_triangles,
         }
         .into();
         callback(&mut polyanya_mesh);
 
         let mut navmesh = Self::from_polyanya_mesh(polyanya_mesh);
         navmesh.transform = Transform::from_rotation(rotation);
-        navmesh
-    }
-
-    /// Creates a [`NavMesh`] from a Bevy [`Mesh`], assuming it constructs a 2D structure.
-    /// All triangle normals are aligned during the conversion, so the orientation of the [`Mesh`] does not matter.
-    ///
-    /// Only supports meshes with the [`PrimitiveTopology::TriangleList`].
-    pub fn from_bevy_mesh(mesh: &Mesh) -> NavMesh {
-        Self::from_bevy_mesh_and_then(mesh, |_| {})
+        navmesh.heights = heights;
+        navmesh.areas = Arc::new(
+            regions
+                .into_iter()
+                .map(|(area, triangles)| (area, builder::union_all(triangles)))
+                .collect(),
+        );
+        Ok(navmesh)
     }
 
     /// Get the underlying Polyanya navigation mesh
@@ -112,9 +458,17 @@ impl NavMesh {
     }
 
     /// Get a path between two points, in an async way
+    ///
+    /// Paths avoid costly areas, see [`NavMesh::set_area_cost`], and can go through off-mesh
+    /// links, see [`NavMesh::path_with_links`].
+    ///
+    /// With walkable area types of different costs, the search runs on a mesh split along the
+    /// areas and only samples a few points on each edge it crosses, so the path found can cost
+    /// slightly more than the cheapest one. With a single cost, it's the shortest path.
     #[inline]
     pub async fn get_path(&self, from: Vec2, to: Vec2) -> Option<Path> {
-        self.mesh.get_path(from, to).await
+        let path = self.get_linked_path(from, to, &[]).await;
+        path.map(|(path, _)| path)
     }
 
     /// Get a path between two points, in an async way.
@@ -123,14 +477,21 @@ impl NavMesh {
     pub async fn get_transformed_path(&self, from: Vec3, to: Vec3) -> Option<TransformedPath> {
         let inner_from = self.transform.transform_point(from).xy();
         let inner_to = self.transform.transform_point(to).xy();
-        let path = self.mesh.get_path(inner_from, inner_to).await;
-        path.map(|path| self.transform_path(path, from, to))
+        let path = self.get_linked_path(inner_from, inner_to, &[]).await;
+        path.map(|(path, links)| self.transform_path(path, links, inner_from))
     }
 
     /// Get a path between two points
+    ///
+    /// Paths avoid costly areas, see [`NavMesh::set_area_cost`], and can go through off-mesh
+    /// links, see [`NavMesh::path_with_links`].
+    ///
+    /// With walkable area types of different costs, the search runs on a mesh split along the
+    /// areas and only samples a few points on each edge it crosses, so the path found can cost
+    /// slightly more than the cheapest one. With a single cost, it's the shortest path.
     #[inline]
     pub fn path(&self, from: Vec2, to: Vec2) -> Option<Path> {
-        self.mesh.path(from, to)
+        self.linked_path(from, to, &[]).map(|(path, _)| path)
     }
 
     /// Get a path between two points, in an async way.
@@ -139,19 +500,31 @@ impl NavMesh {
     pub fn transformed_path(&self, from: Vec3, to: Vec3) -> Option<TransformedPath> {
         let inner_from = self.transform.transform_point(from).xy();
         let inner_to = self.transform.transform_point(to).xy();
-        let path = self.mesh.path(inner_from, inner_to);
-        path.map(|path| self.transform_path(path, from, to))
+        let path = self.linked_path(inner_from, inner_to, &[]);
+        path.map(|(path, links)| self.transform_path(path, links, inner_from))
     }
 
-    fn transform_path(&self, path: Path, from: Vec3, to: Vec3) -> TransformedPath {
+    fn transform_path(
+        &self,
+        path: Path,
+        links: Vec<links::TakenLink>,
+        from: Vec2,
+    ) -> TransformedPath {
         let inverse_transform = self.inverse_transform();
+        let path: Vec<Vec3> = path
+            .path
+            .into_iter()
+            .map(|coords| inverse_transform.transform_point3(coords.extend(self.height_at(coords))))
+            .collect();
+        let start = inverse_transform.transform_point3(from.extend(self.height_at(from)));
         TransformedPath {
-            length: from.distance(to),
-            path: path
-                .path
-                .into_iter()
-                .map(|coords| inverse_transform.transform_point((coords, 0.).into()))
-                .collect(),
+            length: std::iter::once(start)
+                .chain(path.iter().copied())
+                .tuple_windows()
+                .map(|(a, b)| a.distance(b))
+                .sum(),
+            path,
+            links,
         }
     }
 
@@ -166,12 +539,107 @@ impl NavMesh {
         self.mesh.point_in_mesh(point)
     }
 
+    /// The point of the mesh closest to `point`, or `point` itself if it is already in the mesh.
+    ///
+    /// Returns `None` only if the mesh is empty.
+    pub fn closest_point(&self, point: Vec2) -> Option<Vec2> {
+        if self.polygon_at(point).is_some() {
+            return Some(point);
+        }
+        // Look for the boundary in a square around the point, growing until it's found
+        let mut reach = self.mesh.delta().max(f32::EPSILON);
+        loop {
+            let closest = self.closest_boundary_point(point, reach);
+            if closest.is_some() || reach.is_infinite() {
+                return closest;
+            }
+            reach *= 2.0;
+        }
+    }
+
+    /// The point of the mesh closest to `point`, if it is at most `max_distance` away.
+    pub fn closest_point_within(&self, point: Vec2, max_distance: f32) -> Option<Vec2> {
+        if self.polygon_at(point).is_some() {
+            return Some(point);
+        }
+        self.closest_boundary_point(point, max_distance)
+    }
+
+    /// The point on the boundary of the mesh closest to `point`, if it is at most `max_distance`
+    /// away.
+    ///
+    /// Only the edges in a square of `max_distance` around the point are checked.
+    fn closest_boundary_point(&self, point: Vec2, max_distance: f32) -> Option<Vec2> {
+        let reach = Vec2::splat(max_distance);
+        self.boundary_index
+            .within(point - reach, point + reach)
+            .map(|edge| {
+                let (a, b) = self.boundary[edge];
+                let edge = b - a;
+                let along = ((point - a).dot(edge) / edge.length_squared()).clamp(0.0, 1.0);
+                if along.is_finite() {
+                    a + edge * along
+                } else {
+                    a
+                }
+            })
+            .filter(|closest| closest.distance(point) <= max_distance)
+            .min_by(|a, b| {
+                a.distance_squared(point)
+                    .total_cmp(&b.distance_squared(point))
+            })
+    }
+
+    /// The point of the mesh closest to a 3d point, using the [`NavMesh::transform`].
+    ///
+    /// The point is snapped in the plane of the mesh, and placed on its surface.
+    pub fn transformed_closest_point(&self, point: Vec3) -> Option<Vec3> {
+        let inner = self.transform.transform_point(point).xy();
+        self.closest_point(inner)
+            .map(|closest| self.to_surface(closest))
+    }
+
+    /// The point of the mesh closest to a 3d point, using the [`NavMesh::transform`], if it is
+    /// at most `max_distance` away in the plane of the mesh, in world units.
+    ///
+    /// With a non uniform scale, the closest point is searched in mesh coordinates, so it may not
+    /// be the closest one in world units.
+    pub fn transformed_closest_point_within(&self, point: Vec3, max_distance: f32) -> Option<Vec3> {
+        let inner = self.transform.transform_point(point).xy();
+        let inverse = self.inverse_transform();
+        // No distance grows more than the largest scale factor going into mesh coordinates
+        self.closest_point_within(
+            inner,
+            max_distance * self.transform.scale.abs().max_element(),
+        )
+        .filter(|closest| {
+            inverse
+                .transform_vector3((*closest - inner).extend(0.0))
+                .length()
+                <= max_distance
+        })
+        .map(|closest| self.to_surface(closest))
+    }
+
+    /// Converts a point in mesh coordinates to a 3d point on the surface of the mesh.
+    fn to_surface(&self, point: Vec2) -> Vec3 {
+        self.inverse_transform()
+            .transform_point3(point.extend(self.height_at(point)))
+    }
+
     /// The transform used to convert world coordinates into mesh coordinates.
     /// After applying this transform, the `z` coordinate is dropped because navmeshes are 2D.
     pub fn transform(&self) -> Transform {
         self.transform
     }
 
+    /// Radius of the agents this mesh is made for, in mesh coordinates.
+    ///
+    /// Paths keep at least this distance from the edges of the mesh. See [`NavMesh::eroded`].
+    pub fn agent_radius(&self) -> f32 {
+        self.agent_radius
+    }
+
     /// Set the mesh transform
     ///
     /// It will be used to transform a 3d point to a 2d point where the `z` axis can be ignored
@@ -188,8 +656,8 @@ impl NavMesh {
             self.mesh
                 .vertices
                 .iter()
-                .map(|v| [v.coords.x, v.coords.y, 0.0])
-                .map(|coords| inverse_transform.transform_point(coords.into()).into())
+                .map(|v| v.coords.extend(self.height_at(v.coords)))
+                .map(|coords| inverse_transform.transform_point3(coords).into())
                 .collect::<Vec<[f32; 3]>>(),
         );
         new_mesh.insert_indices(Indices::U32(
@@ -214,8 +682,8 @@ impl NavMesh {
             self.mesh
                 .vertices
                 .iter()
-                .map(|v| [v.coords.x, v.coords.y, 0.0])
-                .map(|coords| inverse_transform.transform_point(coords.into()).into())
+                .map(|v| v.coords.extend(self.height_at(v.coords)))
+                .map(|coords| inverse_transform.transform_point3(coords).into())
                 .collect::<Vec<[f32; 3]>>(),
         );
         new_mesh.insert_indices(Indices::U32(
@@ -233,30 +701,129 @@ impl NavMesh {
         new_mesh
     }
 
+    /// Replaces the mesh, updating what is computed from it: islands, spatial indexes and caches.
+    pub(crate) fn set_mesh(&mut self, mesh: Arc<polyanya::Mesh>) {
+        self.islands = Arc::new(islands::find_islands(&mesh));
+        self.polygon_index = Arc::new(spatial::SpatialIndex::new(&spatial::polygon_bounds(&mesh)));
+        let boundary = islands::boundary_edges(&mesh);
+        self.boundary_index = Arc::new(spatial::SpatialIndex::new(
+            &boundary
+                .iter()
+                .map(|(a, b)| (a.min(*b), a.max(*b)))
+                .collect::<Vec<_>>(),
+        ));
+        self.boundary = Arc::new(boundary);
+        self.area_triangles = Arc::new(random::AreaTriangles::new(&mesh));
+        self.mesh = mesh;
+        self.filtered = Default::default();
+        self.costed = Default::default();
+        self.walks = Default::default();
+        self.update_link_islands();
+    }
+
+    /// The affine transform converting mesh coordinates back into world coordinates.
     #[inline]
-    fn inverse_transform(&self) -> Transform {
-        Transform {
-            translation: -self.transform.translation,
-            rotation: self.transform.rotation.inverse(),
-            scale: 1.0 / self.transform.scale,
+    fn inverse_transform(&self) -> Affine3A {
+        self.transform.compute_affine().inverse()
+    }
+}
+
+/// Index of a vertex of `positions` closer than [`WELDING_DISTANCE`] to `vertex`, adding it if
+/// there is none. `cells` lists the vertices in each cube of the welding distance.
+fn weld(
+    cells: &mut HashMap<I64Vec3, Vec<usize>>,
+    positions: &mut Vec<Vec3>,
+    vertex: Vec3,
+) -> usize {
+    let cell = (vertex / WELDING_DISTANCE).floor().as_i64vec3();
+    // Close vertices can be on both sides of the boundary between two cells
+    let neighbours = (-1..=1).flat_map(|x| {
+        (-1..=1).flat_map(move |y| (-1..=1).map(move |z| cell + I64Vec3::new(x, y, z)))
+    });
+    let close = neighbours
+        .filter_map(|neighbour| cells.get(&neighbour))
+        .flatten()
+        .find(|index| positions[**index].distance(vertex) <= WELDING_DISTANCE);
+    if let Some(index) = close {
+        return *index;
+    }
+    positions.push(vertex);
+    cells.entry(cell).or_default().push(positions.len() - 1);
+    positions.len() - 1
+}
+
+/// Error that can happen while building a [`NavMesh`] from a Bevy [`Mesh`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NavMeshBuildError {
+    /// The mesh doesn't use [`PrimitiveTopology::TriangleList`]
+    UnsupportedTopology(PrimitiveTopology),
+    /// The mesh has no indices
+    MissingIndices,
+    /// An index refers to a vertex that doesn't exist
+    IndexOutOfBounds(usize),
+    /// The mesh is missing a required attribute
+    MissingAttribute(&'static str),
+    /// An attribute of the mesh is not in the expected format, like
+    /// [`VertexAttributeValues::Float32x3`] for positions and normals, or has invalid values
+    UnsupportedAttributeFormat(&'static str),
+    /// The mesh has no vertices
+    EmptyMesh,
+    /// The polygons could not be triangulated
+    InvalidPolygons,
+    /// The pixels of an image in this format could not be read
+    UnsupportedImageFormat(TextureFormat),
+    /// A setting has a value that can't be used, like a size that's not positive
+    InvalidSetting(&'static str),
+}
+
+impl Display for NavMeshBuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            NavMeshBuildError::UnsupportedTopology(topology) => {
+                write!(f, "unsupported primitive topology {:?}", topology)
+            }
+            NavMeshBuildError::MissingIndices => write!(f, "no polygon indices found in mesh"),
+            NavMeshBuildError::IndexOutOfBounds(index) => {
+                write!(f, "index {} refers to a missing vertex", index)
+            }
+            NavMeshBuildError::MissingAttribute(name) => {
+                write!(f, "missing attribute {}", name)
+            }
+            NavMeshBuildError::UnsupportedAttributeFormat(name) => {
+                write!(f, "attribute {} is not in a supported format", name)
+            }
+            NavMeshBuildError::EmptyMesh => write!(f, "mesh has no vertices"),
+            NavMeshBuildError::InvalidPolygons => write!(f, "polygons could not be triangulated"),
+            NavMeshBuildError::UnsupportedImageFormat(format) => {
+                write!(f, "unsupported image format {:?}", format)
+            }
+            NavMeshBuildError::InvalidSetting(name) => write!(f, "invalid value for {}", name),
         }
     }
 }
 
+impl Error for NavMeshBuildError {}
+
 fn get_vectors(
     mesh: &Mesh,
-    id: impl Into<MeshVertexAttributeId>,
-) -> impl Iterator<Item = Vec3> + '_ {
-    let vectors = match mesh.attribute(id).unwrap() {
-        VertexAttributeValues::Float32x3(values) => values,
-        // Guaranteed by Bevy
-        _ => unreachable!(),
+    attribute: MeshVertexAttribute,
+) -> Result<impl Iterator<Item = Vec3> + '_, NavMeshBuildError> {
+    let vectors = match mesh.attribute(attribute.id) {
+        Some(VertexAttributeValues::Float32x3(values)) => values,
+        Some(_) => {
+            return Err(NavMeshBuildError::UnsupportedAttributeFormat(
+                attribute.name,
+            ))
+        }
+        None => return Err(NavMeshBuildError::MissingAttribute(attribute.name)),
     };
-    vectors.iter().cloned().map(Vec3::from)
+    Ok(vectors.iter().cloned().map(Vec3::from))
 }
 
 #[cfg(test)]
 mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
     use polyanya::Trimesh;
 
     use super::*;
@@ -277,12 +844,7 @@ mod tests {
             }
             .into(),
         );
-        let mut bevy_mesh = expected_navmesh.to_mesh();
-        // Add back normals as they are used to determine where is up in the mesh
-        bevy_mesh.insert_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            (0..6).map(|_| [0.0, 0.0, 1.0]).collect::<Vec<_>>(),
-        );
+        let bevy_mesh = expected_navmesh.to_mesh();
         let actual_navmesh = NavMesh::from_bevy_mesh(&bevy_mesh);
 
         assert_same_navmesh(expected_navmesh, actual_navmesh);
@@ -328,6 +890,345 @@ mod tests {
         assert_same_navmesh(expected_navmesh, actual_navmesh);
     }
 
+    #[test]
+    fn transformed_path_length_follows_the_path() {
+        let mut navmesh = NavMesh::from_polyanya_mesh(
+            Trimesh {
+                vertices: vec![
+                    Vec2::new(0., 0.),
+                    Vec2::new(3., 0.),
+                    Vec2::new(3., 3.),
+                    Vec2::new(2., 3.),
+                    Vec2::new(2., 1.),
+                    Vec2::new(0., 1.),
+                ],
+                triangles: vec![[0, 1, 4], [0, 4, 5], [1, 2, 4], [4, 2, 3]],
+            }
+            .into(),
+        );
+        navmesh.set_transform(Transform::from_scale(Vec3::splat(0.5)));
+
+        let path = navmesh
+            .transformed_path(Vec3::new(1., 1., 0.), Vec3::new(5., 5., 0.))
+            .unwrap();
+
+        assert_eq!(
+            path.path,
+            vec![Vec3::new(4., 2., 0.), Vec3::new(5., 5., 0.)]
+        );
+        let expected_length = 2.0 * Vec2::new(3., 1.).length();
+        assert!((path.length - expected_length).abs() < 1e-5);
+    }
+
+    #[test]
+    fn transformed_path_round_trips_through_transform() {
+        let mut navmesh = NavMesh::from_polyanya_mesh(
+            Trimesh {
+                vertices: vec![
+                    Vec2::new(0., 0.),
+                    Vec2::new(10., 0.),
+                    Vec2::new(10., 10.),
+                    Vec2::new(0., 10.),
+                ],
+                triangles: vec![[0, 1, 2], [0, 2, 3]],
+            }
+            .into(),
+        );
+        for transform in [
+            Transform::from_xyz(5., -3., 2.),
+            Transform::from_xyz(5., -3., 2.).with_scale(Vec3::new(2., 0.5, 1.)),
+            Transform::from_xyz(5., -3., 2.).with_rotation(Quat::from_rotation_x(1.2)),
+            Transform::from_xyz(-4., 7., 1.)
+                .with_rotation(Quat::from_euler(EulerRot::XYZ, 0.3, -1.1, 2.0))
+                .with_scale(Vec3::new(0.5, 3., 2.)),
+        ] {
+            navmesh.set_transform(transform);
+            let to_world = transform.compute_affine().inverse();
+            let from = to_world.transform_point3(Vec3::new(1., 2., 0.));
+            let to = to_world.transform_point3(Vec3::new(8., 9., 0.));
+
+            let path = navmesh.transformed_path(from, to).unwrap();
+
+            assert!(
+                path.path.last().unwrap().distance(to) < 1e-4,
+                "{transform:?}: expected path to end at {to}, got {:?}",
+                path.path
+            );
+            assert!((path.length - from.distance(to)).abs() < 1e-4);
+            assert!(navmesh.transformed_is_in_mesh(from));
+        }
+    }
+
+    #[test]
+    fn invalid_meshes_are_reported() {
+        let mut bevy_mesh = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        assert_eq!(
+            NavMesh::try_from_bevy_mesh(&bevy_mesh).unwrap_err(),
+            NavMeshBuildError::MissingAttribute(Mesh::ATTRIBUTE_POSITION.name)
+        );
+
+        bevy_mesh.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+        );
+        assert_eq!(
+            NavMesh::try_from_bevy_mesh(&bevy_mesh).unwrap_err(),
+            NavMeshBuildError::MissingIndices
+        );
+
+        bevy_mesh.insert_indices(Indices::U32(vec![0, 1, 3]));
+        assert_eq!(
+            NavMesh::try_from_bevy_mesh(&bevy_mesh).unwrap_err(),
+            NavMeshBuildError::IndexOutOfBounds(3)
+        );
+
+        bevy_mesh.insert_indices(Indices::U32(vec![0, 1, 2]));
+        assert!(NavMesh::try_from_bevy_mesh(&bevy_mesh).is_ok());
+
+        let line_mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::all());
+        assert_eq!(
+            NavMesh::try_from_bevy_mesh(&line_mesh).unwrap_err(),
+            NavMeshBuildError::UnsupportedTopology(PrimitiveTopology::LineList)
+        );
+    }
+
+    #[test]
+    fn up_axis_and_slope_select_walkable_triangles() {
+        // A floor, a ramp at 45° and a wall, with misleading normals
+        let mut level = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        level.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [4.0, 0.0, 0.0],
+                [4.0, 0.0, -4.0],
+                [0.0, 0.0, -4.0],
+                [5.0, 1.0, 0.0],
+                [5.0, 1.0, -4.0],
+                [5.0, 3.0, 0.0],
+                [5.0, 3.0, -4.0],
+            ],
+        );
+        level.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[1.0, 0.0, 0.0]; 8]);
+        let floor_and_ramp = vec![0, 1, 2, 0, 2, 3, 1, 4, 5, 1, 5, 2];
+        level.insert_indices(Indices::U32(floor_and_ramp.clone()));
+
+        let navmesh = NavMesh::from_bevy_mesh(&level);
+        assert!(navmesh.transformed_is_in_mesh(Vec3::new(4.5, 0.5, -2.0)));
+        let path = navmesh
+            .transformed_path(Vec3::new(1.0, 0.0, -1.0), Vec3::new(4.9, 0.9, -1.0))
+            .unwrap();
+        assert!(path.path[0].distance(Vec3::new(4.9, 0.9, -1.0)) < 1e-4);
+
+        level.insert_indices(Indices::U32(
+            floor_and_ramp
+                .into_iter()
+                .chain([4, 6, 7, 4, 7, 5])
+                .collect(),
+        ));
+        let navmesh = NavMesh::try_from_bevy_meshes_with_settings(
+            &[(&level, GlobalTransform::IDENTITY)],
+            &FromMeshSettings {
+                up: Some(Vec3::Y),
+                max_slope: Some(30f32.to_radians()),
+            },
+            |_| {},
+        )
+        .unwrap();
+        assert!(navmesh
+            .transform()
+            .rotation
+            .abs_diff_eq(Quat::from_rotation_x(FRAC_PI_2), 1e-6));
+        assert!(navmesh.transformed_is_in_mesh(Vec3::new(2.0, 0.0, -2.0)));
+        assert!(!navmesh.transformed_is_in_mesh(Vec3::new(4.5, 0.5, -2.0)));
+    }
+
+    #[test]
+    fn meshes_are_merged_into_one_navmesh() {
+        let mut quad = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        quad.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 0.0, -1.0],
+                [0.0, 0.0, -1.0],
+            ],
+        );
+        quad.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 4]);
+        quad.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+
+        let navmesh = NavMesh::from_bevy_meshes(&[
+            (&quad, GlobalTransform::IDENTITY),
+            (&quad, GlobalTransform::from_xyz(1.0, 0.0, 0.0)),
+            (
+                &quad,
+                Transform::from_xyz(3.0, 0.0, 0.0)
+                    .with_scale(Vec3::new(-1.0, 1.0, 1.0))
+                    .into(),
+            ),
+        ]);
+
+        assert_eq!(navmesh.get().vertices.len(), 8);
+        assert_eq!(navmesh.get().polygons.len(), 6);
+        let path = navmesh
+            .transformed_path(Vec3::new(0.1, 0.0, -0.1), Vec3::new(2.9, 0.0, -0.9))
+            .unwrap();
+        assert!(
+            path.path
+                .last()
+                .unwrap()
+                .distance(Vec3::new(2.9, 0.0, -0.9))
+                < 1e-5
+        );
+        assert!((path.length - Vec2::new(2.8, 0.8).length()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn vertices_are_welded_by_distance() {
+        let mut quad = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        quad.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 0.0, -1.0],
+                [0.0, 0.0, -1.0],
+            ],
+        );
+        quad.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+
+        // Close vertices on both sides of a cell boundary are welded
+        let navmesh = NavMesh::from_bevy_meshes(&[
+            (&quad, GlobalTransform::from_xyz(-0.99995, 0.0, 0.0)),
+            (&quad, GlobalTransform::from_xyz(0.00004, 0.0, 0.0)),
+        ]);
+        assert_eq!(navmesh.get().vertices.len(), 6);
+
+        // Far from the origin, distinct vertices stay apart
+        let navmesh =
+            NavMesh::from_bevy_meshes(&[(&quad, GlobalTransform::from_xyz(300_000.0, 0.0, 0.0))]);
+        assert_eq!(navmesh.get().vertices.len(), 4);
+        assert_eq!(navmesh.get().polygons.len(), 2);
+    }
+
+    #[test]
+    fn transformed_path_follows_terrain_height() {
+        // A hill rising along -z
+        let mut hill = Mesh::new(PrimitiveTopology::TriangleList, RenderAssetUsages::all());
+        hill.insert_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [2.0, 0.0, 0.0],
+                [2.0, 1.0, -2.0],
+                [0.0, 1.0, -2.0],
+            ],
+        );
+        hill.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 1.0, 0.0]; 4]);
+        hill.insert_indices(Indices::U32(vec![0, 1, 2, 0, 2, 3]));
+
+        let navmesh = NavMesh::try_from_bevy_meshes_with_settings(
+            &[(&hill, GlobalTransform::IDENTITY)],
+            &FromMeshSettings {
+                up: Some(Vec3::Y),
+                ..default()
+            },
+            |_| {},
+        )
+        .unwrap();
+        let path = navmesh
+            .transformed_path(Vec3::new(0.5, 0.0, -0.5), Vec3::new(1.5, 0.0, -1.5))
+            .unwrap();
+        assert!(path.path[0].distance(Vec3::new(1.5, 0.75, -1.5)) < 1e-5);
+        assert!((path.length - Vec3::new(1.0, 0.5, 1.0).length()).abs() < 1e-5);
+
+        let VertexAttributeValues::Float32x3(positions) = navmesh
+            .to_mesh()
+            .attribute(Mesh::ATTRIBUTE_POSITION)
+            .unwrap()
+            .clone()
+        else {
+            panic!("unexpected positions format");
+        };
+        for position in positions {
+            let position = Vec3::from(position);
+            assert!((position.y + position.z / 2.0).abs() < 1e-5, "{position}");
+        }
+    }
+
+    #[test]
+    fn points_outside_are_snapped_to_closest_point() {
+        let mut navmesh = NavMesh::from_polyanya_mesh(
+            Trimesh {
+                vertices: vec![
+                    Vec2::new(0., 0.),
+                    Vec2::new(4., 0.),
+                    Vec2::new(4., 4.),
+                    Vec2::new(0., 4.),
+                ],
+                triangles: vec![[0, 1, 2], [0, 2, 3]],
+            }
+            .into(),
+        );
+
+        assert_eq!(
+            navmesh.closest_point(Vec2::new(1., 2.)),
+            Some(Vec2::new(1., 2.))
+        );
+        assert_eq!(
+            navmesh.closest_point(Vec2::new(-3., 2.)),
+            Some(Vec2::new(0., 2.))
+        );
+        assert_eq!(
+            navmesh.closest_point(Vec2::new(6., 7.)),
+            Some(Vec2::new(4., 4.))
+        );
+        assert_eq!(navmesh.closest_point_within(Vec2::new(-3., 2.), 2.), None);
+        assert_eq!(
+            navmesh.closest_point_within(Vec2::new(-1., 2.), 2.),
+            Some(Vec2::new(0., 2.))
+        );
+
+        navmesh.set_transform(Transform::from_rotation(Quat::from_rotation_x(FRAC_PI_2)));
+        let closest = navmesh
+            .transformed_closest_point(Vec3::new(5., 3., -2.))
+            .unwrap();
+        assert!(closest.distance(Vec3::new(4., 0., -2.)) < 1e-5, "{closest}");
+        assert!(navmesh
+            .transformed_closest_point_within(Vec3::new(5., 3., -2.), 0.5)
+            .is_none());
+
+        // Distances are in world units, a mesh unit is half a world unit
+        navmesh.set_transform(Transform::from_scale(Vec3::splat(2.)));
+        assert_eq!(
+            navmesh.transformed_closest_point_within(Vec3::new(-0.75, 1., 0.), 1.),
+            Some(Vec3::new(0., 1., 0.))
+        );
+        assert_eq!(
+            navmesh.transformed_closest_point_within(Vec3::new(-1.5, 1., 0.), 1.),
+            None
+        );
+    }
+
+    #[test]
+    fn closest_point_is_on_the_boundary() {
+        // The diagonal between the two triangles is not part of the boundary
+        let navmesh = test_fixtures::square_navmesh();
+        assert_eq!(navmesh.boundary.len(), 4);
+        assert_eq!(
+            navmesh.closest_point(Vec2::new(11., 11.)),
+            Some(Vec2::new(10., 10.))
+        );
+        assert_eq!(
+            navmesh.closest_point(Vec2::new(-100., 5.)),
+            Some(Vec2::new(0., 5.))
+        );
+        assert!(NavMesh::from_polyanya_mesh(polyanya::Mesh::default())
+            .closest_point(Vec2::ZERO)
+            .is_none());
+    }
+
     fn assert_same_navmesh(expected: NavMesh, actual: NavMesh) {
         let expected_mesh = expected.mesh;
         let actual_mesh = actual.mesh;
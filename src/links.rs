@@ -0,0 +1,504 @@
+//! Off-mesh links, connecting points of a [`NavMesh`] that don't share an edge
+
+use std::sync::{Arc, RwLock};
+
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::future::yield_now;
+use bevy::utils::HashMap;
+use itertools::Itertools;
+use polyanya::Path;
+
+use crate::{NavMesh, TransformedPath};
+
+/// A connection between two points of a [`NavMesh`] that is not a shared polygon edge, like a
+/// jump, a ladder, a teleporter or a door.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OffMeshLink {
+    /// Where the link starts, in mesh coordinates.
+    pub start: Vec2,
+    /// Where the link ends, in mesh coordinates.
+    pub end: Vec2,
+    /// If the link can also be taken from `end` to `start`.
+    pub bidirectional: bool,
+    /// Cost of taking the link, compared to the cost of walking on the mesh.
+    pub cost: f32,
+    /// Tag to identify the kind of link, for example to play the matching animation.
+    pub tag: u32,
+}
+
+/// An [`OffMeshLink`] taken by a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TakenLink {
+    /// Index of the waypoint reached by taking the link. The link is taken from the previous
+    /// waypoint, or from the start of the path for the first waypoint.
+    pub waypoint: usize,
+    /// Index of the link in [`NavMesh::links`], or in
+    /// [`LayeredNavMesh::connections`](crate::layers::LayeredNavMesh::connections) for a path
+    /// between layers.
+    pub link: usize,
+    /// Tag of the link.
+    pub tag: u32,
+}
+
+/// Paths on the mesh between the ends of the off-mesh links, by index of the ends (the start
+/// then the end of each link), with their cost. They are searched when a query first needs them,
+/// and kept for all the queries without the same area types.
+pub(crate) type Walks = HashMap<(usize, usize), Option<(Vec<Vec2>, f32)>>;
+
+/// How a node of [`search_links`] is reached from the previous one.
+#[derive(Debug)]
+enum Step<P> {
+    Walk(Vec<P>),
+    /// Taking a link, to one of its ends
+    Link(usize, P),
+}
+
+/// A link between two nodes of [`search_links`].
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct GraphLink {
+    pub(crate) cost: f32,
+    pub(crate) bidirectional: bool,
+    pub(crate) tag: u32,
+}
+
+impl NavMesh {
+    /// Adds an off-mesh link, returning its index.
+    ///
+    /// On a mesh with obstacles, see [`NavMesh::set_obstacles`], the ends of the link are moved
+    /// out of the space kept around the obstacles.
+    pub fn add_link(&mut self, link: OffMeshLink) -> usize {
+        self.keep_walks(|_| true);
+        let link = match &mut self.uncut {
+            Some(uncut) => {
+                uncut.links.push(link);
+                self.out_of_obstacles(link)
+            }
+            None => link,
+        };
+        self.link_islands.push(self.link_ends_islands(&link));
+        self.links.push(link);
+        self.connect_islands();
+        self.links.len() - 1
+    }
+
+    /// Replaces the off-mesh link at `index`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no link at `index`.
+    pub fn set_link(&mut self, index: usize, link: OffMeshLink) {
+        self.keep_walks(|end| end / 2 != index);
+        let link = match &mut self.uncut {
+            Some(uncut) => {
+                uncut.links[index] = link;
+                self.out_of_obstacles(link)
+            }
+            None => link,
+        };
+        self.link_islands[index] = self.link_ends_islands(&link);
+        self.links[index] = link;
+        self.connect_islands();
+    }
+
+    /// Keeps the cached walks between ends for which `keep` is true, in a new cache so that the
+    /// clones of this mesh, sharing the current one, are not affected.
+    fn keep_walks(&mut self, keep: impl Fn(usize) -> bool) {
+        let walks = self
+            .walks
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(excluded, walks)| {
+                let kept = walks
+                    .iter()
+                    .filter(|((from, to), _)| keep(*from) && keep(*to))
+                    .map(|(ends, walk)| (*ends, walk.clone()))
+                    .collect();
+                (excluded.clone(), kept)
+            })
+            .collect();
+        self.walks = Arc::new(RwLock::new(walks));
+    }
+
+    /// Island of the start and of the end of a link.
+    pub(crate) fn link_ends_islands(&self, link: &OffMeshLink) -> [Option<usize>; 2] {
+        [self.island_of(link.start), self.island_of(link.end)]
+    }
+
+    /// The off-mesh links of this mesh.
+    pub fn links(&self) -> &[OffMeshLink] {
+        &self.links
+    }
+
+    /// Get a path between two points, and the off-mesh links it takes.
+    ///
+    /// The search minimizes the cost of the path, but its `length` is the distance travelled,
+    /// including along the links taken. See [`NavMesh::path_cost`] for the cost on the mesh.
+    pub fn path_with_links(&self, from: Vec2, to: Vec2) -> Option<(Path, Vec<TakenLink>)> {
+        self.linked_path(from, to, &[])
+    }
+
+    /// Finds the cheapest path between two points, without the `excluded` area types, going
+    /// through off-mesh links when needed.
+    ///
+    /// Nodes of the search are the start, the destination and the ends of the links. They are
+    /// connected by paths on the mesh, and by the links themselves. Paths between the ends of
+    /// the links are searched once, see [`NavMesh::walk_between_ends`], and nodes on other
+    /// islands than the start or the destination are not searched from or to. Without links,
+    /// this is a path search on the mesh.
+    pub(crate) fn linked_path(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        excluded: &[u8],
+    ) -> Option<(Path, Vec<TakenLink>)> {
+        if self.links.is_empty() {
+            // Polyanya already bails out early for points on different islands
+            return self
+                .cheapest_path(from, to, excluded)
+                .map(|path| (path, vec![]));
+        }
+        self.costed_linked_path(from, to, excluded)
+            .map(|(path, taken, _)| (path, taken))
+    }
+
+    /// Same as [`NavMesh::linked_path`], in an async way.
+    ///
+    /// Without links, the search on the mesh yields regularly, see
+    /// [`polyanya::Mesh::get_path`]. With links, the search yields once then runs to completion.
+    pub(crate) async fn get_linked_path(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        excluded: &[u8],
+    ) -> Option<(Path, Vec<TakenLink>)> {
+        if self.links.is_empty() {
+            return self
+                .get_cheapest_path(from, to, excluded)
+                .await
+                .map(|path| (path, vec![]));
+        }
+        yield_now().await;
+        self.linked_path(from, to, excluded)
+    }
+
+    /// Same as [`NavMesh::linked_path`], also returning the cost of the path.
+    pub(crate) fn costed_linked_path(
+        &self,
+        from: Vec2,
+        to: Vec2,
+        excluded: &[u8],
+    ) -> Option<(Path, Vec<TakenLink>, f32)> {
+        if self.links.is_empty() {
+            let path = self.cheapest_path(from, to, excluded)?;
+            let cost = self.path_cost(from, &path);
+            return Some((path, vec![], cost));
+        }
+        let (Some(from_island), Some(to_island)) = (self.island_of(from), self.island_of(to))
+        else {
+            return None;
+        };
+        if !self.is_island_reachable(from_island, to_island) {
+            return None;
+        }
+
+        let points = [from, to]
+            .into_iter()
+            .chain(self.links.iter().flat_map(|link| [link.start, link.end]))
+            .collect::<Vec<_>>();
+        let islands = [Some(from_island), Some(to_island)]
+            .into_iter()
+            .chain(self.link_islands.iter().flatten().copied())
+            .collect::<Vec<_>>();
+        let walk = |current: usize, next: usize| {
+            if current >= 2 && next >= 2 {
+                return self.walk_between_ends(current - 2, next - 2, excluded);
+            }
+            if islands[current].is_none() || islands[current] != islands[next] {
+                return None;
+            }
+            let path = self.cheapest_path(points[current], points[next], excluded)?;
+            let cost = self.path_cost(points[current], &path);
+            Some((path.path, cost))
+        };
+        let links = self
+            .links
+            .iter()
+            .map(|link| GraphLink {
+                cost: link.cost,
+                bidirectional: link.bidirectional,
+                tag: link.tag,
+            })
+            .collect::<Vec<_>>();
+        let (path, taken, cost) = search_links(&points, &links, walk)?;
+        let length = std::iter::once(from)
+            .chain(path.iter().copied())
+            .tuple_windows()
+            .map(|(a, b)| a.distance(b))
+            .sum();
+        Some((Path { length, path }, taken, cost))
+    }
+
+    /// The cheapest path between two points in world coordinates, without the `excluded` area
+    /// types, with its cost.
+    ///
+    /// The cost is scaled from mesh coordinates like the length of the path.
+    pub(crate) fn transformed_costed_path(
+        &self,
+        from: Vec3,
+        to: Vec3,
+        excluded: &[u8],
+    ) -> Option<(TransformedPath, f32)> {
+        let inner_from = self.transform.transform_point(from).xy();
+        let inner_to = self.transform.transform_point(to).xy();
+        let (path, taken, cost) = self.costed_linked_path(inner_from, inner_to, excluded)?;
+        let length = path.length;
+        let path = self.transform_path(path, taken, inner_from);
+        let scale = if length > 0.0 {
+            path.length / length
+        } else {
+            1.0
+        };
+        Some((path, cost * scale))
+    }
+
+    /// The path on the mesh between two ends of the links, by index of the ends (the start then
+    /// the end of each link), without the given area types, with its cost.
+    ///
+    /// It is searched the first time it's needed, then kept until one of the two links changes.
+    /// `excluded` must be sorted.
+    pub(crate) fn walk_between_ends(
+        &self,
+        from: usize,
+        to: usize,
+        excluded: &[u8],
+    ) -> Option<(Vec<Vec2>, f32)> {
+        if let Some(walk) = self
+            .walks
+            .read()
+            .unwrap()
+            .get(excluded)
+            .and_then(|walks| walks.get(&(from, to)))
+        {
+            return walk.clone();
+        }
+
+        let [from_island, to_island] = [from, to].map(|end| self.link_islands[end / 2][end % 2]);
+        if from_island.is_none() || from_island != to_island {
+            return None;
+        }
+        let [start, end] = [from, to].map(|end| {
+            let link = &self.links[end / 2];
+            if end % 2 == 0 {
+                link.start
+            } else {
+                link.end
+            }
+        });
+        let walk = self.cheapest_path(start, end, excluded).map(|path| {
+            let cost = self.path_cost(start, &path);
+            (path.path, cost)
+        });
+        self.walks
+            .write()
+            .unwrap()
+            .entry(excluded.to_vec())
+            .or_default()
+            .insert((from, to), walk.clone());
+        walk
+    }
+}
+
+/// Finds the cheapest way from `points[0]` to `points[1]`, with Dijkstra's algorithm. The other
+/// points are the start then the end of each link, and `walk` gives the waypoints and the cost of
+/// walking from a point to another, if possible.
+///
+/// Returns the waypoints without the start, the links taken and the cost.
+pub(crate) fn search_links<P: Copy + PartialEq>(
+    points: &[P],
+    links: &[GraphLink],
+    mut walk: impl FnMut(usize, usize) -> Option<(Vec<P>, f32)>,
+) -> Option<(Vec<P>, Vec<TakenLink>, f32)> {
+    let mut costs = vec![f32::INFINITY; points.len()];
+    let mut previous: Vec<Option<(usize, Step<P>)>> = points.iter().map(|_| None).collect();
+    let mut done = vec![false; points.len()];
+    costs[0] = 0.0;
+
+    while let Some(current) = (0..points.len())
+        .filter(|node| !done[*node] && costs[*node].is_finite())
+        .min_by(|a, b| costs[*a].total_cmp(&costs[*b]))
+    {
+        if current == 1 {
+            break;
+        }
+        done[current] = true;
+
+        // Links starting from the current node
+        if current >= 2 {
+            let index = (current - 2) / 2;
+            let link = &links[index];
+            let at_start = current % 2 == 0;
+            if at_start || link.bidirectional {
+                let other = if at_start { current + 1 } else { current - 1 };
+                let cost = costs[current] + link.cost;
+                if cost < costs[other] {
+                    costs[other] = cost;
+                    previous[other] = Some((current, Step::Link(index, points[other])));
+                }
+            }
+        }
+
+        // Walking to the destination or to the links that can be taken from their end
+        for next in 1..points.len() {
+            let can_enter = next < 2 || next % 2 == 0 || links[(next - 2) / 2].bidirectional;
+            if done[next] || next == current || !can_enter {
+                continue;
+            }
+            let Some((path, cost)) = walk(current, next) else {
+                continue;
+            };
+            let cost = costs[current] + cost;
+            if cost < costs[next] {
+                costs[next] = cost;
+                previous[next] = Some((current, Step::Walk(path)));
+            }
+        }
+    }
+
+    let mut steps = vec![];
+    let mut node = 1;
+    while let Some((from, step)) = previous[node].take() {
+        steps.push(step);
+        node = from;
+    }
+    if node != 0 {
+        return None;
+    }
+
+    let mut path: Vec<P> = vec![];
+    let mut taken = vec![];
+    for step in steps.into_iter().rev() {
+        match step {
+            Step::Walk(walk) => {
+                for point in walk {
+                    if *path.last().unwrap_or(&points[0]) != point {
+                        path.push(point);
+                    }
+                }
+            }
+            Step::Link(index, end) => {
+                path.push(end);
+                taken.push(TakenLink {
+                    waypoint: path.len() - 1,
+                    link: index,
+                    tag: links[index].tag,
+                });
+            }
+        }
+    }
+    Some((path, taken, costs[1]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::square_with_obstacle;
+
+    const JUMP: u32 = 7;
+
+    /// Two rooms separated by a wall
+    fn two_rooms() -> NavMesh {
+        square_with_obstacle(Vec2::new(4., -1.), Vec2::new(6., 11.))
+    }
+
+    #[test]
+    fn path_takes_link() {
+        let mut navmesh = two_rooms();
+        let from = Vec2::new(1., 1.);
+        let to = Vec2::new(9., 1.);
+        assert!(navmesh.path(from, to).is_none());
+
+        let link = navmesh.add_link(OffMeshLink {
+            start: Vec2::new(3., 5.),
+            end: Vec2::new(7., 5.),
+            bidirectional: false,
+            cost: 4.,
+            tag: JUMP,
+        });
+
+        let (path, taken) = navmesh.path_with_links(from, to).unwrap();
+        assert_eq!(
+            path.path,
+            vec![Vec2::new(3., 5.), Vec2::new(7., 5.), Vec2::new(9., 1.)]
+        );
+        assert_eq!(
+            taken,
+            vec![TakenLink {
+                waypoint: 1,
+                link,
+                tag: JUMP
+            }]
+        );
+        assert!((path.length - (2. * 20f32.sqrt() + 4.)).abs() < 1e-4);
+
+        // The link only goes one way
+        assert!(navmesh.path(to, from).is_none());
+        navmesh.set_link(
+            link,
+            OffMeshLink {
+                bidirectional: true,
+                ..navmesh.links()[link]
+            },
+        );
+        let (path, _) = navmesh.path_with_links(to, from).unwrap();
+        assert_eq!(path.path[1], Vec2::new(3., 5.));
+    }
+
+    #[test]
+    fn cheapest_link_is_taken() {
+        let mut navmesh = two_rooms();
+        for (y, cost) in [(1., 20.), (9., 4.), (5., 100.)] {
+            navmesh.add_link(OffMeshLink {
+                start: Vec2::new(3., y),
+                end: Vec2::new(7., y),
+                bidirectional: false,
+                cost,
+                tag: JUMP,
+            });
+        }
+
+        let (path, taken) = navmesh
+            .path_with_links(Vec2::new(1., 1.), Vec2::new(9., 1.))
+            .unwrap();
+        assert_eq!(path.path[0], Vec2::new(3., 9.));
+        assert_eq!(taken[0].link, 1);
+
+        // Paths between the ends of the links are kept for the next queries, but only searched
+        // when needed, and not between the rooms
+        let cached = |navmesh: &NavMesh| {
+            navmesh.walks.read().unwrap()[&[][..]]
+                .keys()
+                .copied()
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(cached(&navmesh), vec![(0, 2), (0, 4), (4, 2)]);
+        let clone = navmesh.clone();
+
+        // Changing a link only searches again the paths to and from its ends
+        navmesh.set_link(
+            0,
+            OffMeshLink {
+                start: Vec2::new(3., 2.),
+                ..navmesh.links()[0]
+            },
+        );
+        assert_eq!(cached(&navmesh), vec![(4, 2)]);
+        assert_eq!(cached(&clone), vec![(0, 2), (0, 4), (4, 2)]);
+        let (_, taken) = navmesh
+            .path_with_links(Vec2::new(1., 1.), Vec2::new(9., 1.))
+            .unwrap();
+        assert_eq!(taken[0].link, 1);
+        assert_eq!(cached(&navmesh), vec![(0, 2), (0, 4), (4, 2)]);
+    }
+}
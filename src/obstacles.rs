@@ -0,0 +1,507 @@
+//! Obstacles cut out of a [`NavMesh`] at runtime, updated by [`VleueNavigatorPlugin`](crate::VleueNavigatorPlugin)
+
+use std::{
+    f32::consts::TAU,
+    sync::{Arc, RwLock},
+};
+
+use bevy::{
+    prelude::*,
+    tasks::AsyncComputeTaskPool,
+    utils::{HashMap, HashSet},
+};
+use geo::{BooleanOps, Intersects, MultiPolygon, Point};
+
+use crate::{
+    builder::{edges_buffer, mesh_area, to_polygon, triangulate, union_all},
+    links::OffMeshLink,
+    NavMesh, NavMeshBuildError,
+};
+
+/// Number of segments used to approximate a [`ObstacleShape::Circle`].
+const CIRCLE_SEGMENTS: usize = 16;
+
+/// An entity blocking movement on a [`NavMesh`], like a door, a crate or a vehicle.
+///
+/// The shape is in the plane of the navmesh, and placed using the [`GlobalTransform`] of the
+/// entity. When an obstacle is added, moved or removed, the navmesh is cut again on the
+/// [`AsyncComputeTaskPool`], and replaced a few frames later,
+/// which sends an [`AssetEvent::Modified`] for it. Obstacles changing while a navmesh is being
+/// cut are cut together once it's done. Paths being computed for a
+/// [`PathRequest`](crate::pathfinding::PathRequest) on that navmesh are restarted.
+#[derive(Component, Debug, Clone)]
+pub struct NavMeshObstacle {
+    /// Shape of the obstacle.
+    pub shape: ObstacleShape,
+    /// [`NavMesh`] to cut the obstacle from.
+    pub navmesh: Handle<NavMesh>,
+}
+
+/// Shape of a [`NavMeshObstacle`], centered on the entity.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ObstacleShape {
+    /// A rectangle.
+    Rectangle(Rectangle),
+    /// A circle, approximated by a polygon.
+    Circle(Circle),
+    /// A polygon delimited by the list of points on its edges.
+    Polygon(Vec<Vec2>),
+}
+
+impl ObstacleShape {
+    fn points(&self) -> Vec<Vec2> {
+        match self {
+            ObstacleShape::Rectangle(rectangle) => {
+                let Vec2 { x, y } = rectangle.half_size;
+                vec![
+                    Vec2::new(-x, -y),
+                    Vec2::new(x, -y),
+                    Vec2::new(x, y),
+                    Vec2::new(-x, y),
+                ]
+            }
+            ObstacleShape::Circle(circle) => (0..CIRCLE_SEGMENTS)
+                .map(|i| Vec2::from_angle(i as f32 * TAU / CIRCLE_SEGMENTS as f32) * circle.radius)
+                .collect(),
+            ObstacleShape::Polygon(points) => points.clone(),
+        }
+    }
+
+    /// Points of the shape in the coordinates of the `navmesh`.
+    fn to_navmesh(&self, transform: &GlobalTransform, navmesh: &NavMesh) -> Vec<Vec2> {
+        let into_plane = navmesh.transform.rotation.inverse();
+        self.points()
+            .into_iter()
+            .map(|point| transform.transform_point(into_plane * point.extend(0.0)))
+            .map(|point| navmesh.transform.transform_point(point).xy())
+            .collect()
+    }
+}
+
+/// A mesh as it was before obstacles were cut out of it.
+#[derive(Debug, Clone)]
+pub(crate) struct Uncut {
+    pub(crate) mesh: Arc<polyanya::Mesh>,
+    /// Outline of the mesh, so that it's not computed again each time obstacles move
+    pub(crate) area: Arc<MultiPolygon<f32>>,
+    /// Off-mesh links as they were added, before their ends were moved out of obstacles
+    pub(crate) links: Vec<OffMeshLink>,
+    /// The obstacles currently cut out of the mesh
+    obstacles: MultiPolygon<f32>,
+    /// The obstacles, inflated by the radius of the agents
+    covered: MultiPolygon<f32>,
+}
+
+/// Obstacles cut out of a mesh, computed apart from the [`NavMesh`] so that it can be done on
+/// another thread, then applied with [`NavMesh::apply_cut`].
+#[derive(Debug)]
+pub(crate) struct Cut {
+    /// The mesh without obstacles the cut was computed from
+    source: Arc<polyanya::Mesh>,
+    area: Arc<MultiPolygon<f32>>,
+    agent_radius: f32,
+    obstacles: MultiPolygon<f32>,
+    covered: MultiPolygon<f32>,
+    mesh: Arc<polyanya::Mesh>,
+}
+
+impl Cut {
+    /// Cuts `obstacles`, inflated by `agent_radius`, out of `source`. Its outline is computed if
+    /// `area` is not known yet.
+    fn new(
+        source: Arc<polyanya::Mesh>,
+        area: Option<Arc<MultiPolygon<f32>>>,
+        obstacles: &[Vec<Vec2>],
+        agent_radius: f32,
+    ) -> Result<Cut, NavMeshBuildError> {
+        let area = area.unwrap_or_else(|| Arc::new(mesh_area(&source)));
+        let obstacles = union_all(obstacles.iter().map(|obstacle| to_polygon(obstacle)));
+        let covered = if agent_radius > 0.0 && !obstacles.0.is_empty() {
+            obstacles.union(&edges_buffer(&obstacles, agent_radius))
+        } else {
+            obstacles.clone()
+        };
+        let mesh = if obstacles.0.is_empty() {
+            source.clone()
+        } else {
+            let mut mesh = triangulate(&area.difference(&covered), 0.0)?;
+            mesh.set_delta(source.delta());
+            Arc::new(mesh)
+        };
+        Ok(Cut {
+            source,
+            area,
+            agent_radius,
+            obstacles,
+            covered,
+            mesh,
+        })
+    }
+}
+
+impl NavMesh {
+    /// Cuts obstacles out of the mesh, replacing those previously set.
+    ///
+    /// Obstacles are delimited by the list of points on their edges, in mesh coordinates. The
+    /// mesh as it was before the first call is kept, so that removing obstacles restores it.
+    /// Obstacles are inflated by the [`NavMesh::agent_radius`].
+    ///
+    /// Ends of off-mesh links in the space kept around obstacles for the agents are moved to the
+    /// closest point of the mesh. Links with an end inside an obstacle can't be taken until the
+    /// obstacle moves away.
+    pub fn set_obstacles(&mut self, obstacles: &[Vec<Vec2>]) -> Result<(), NavMeshBuildError> {
+        let (source, area) = self.cut_source();
+        self.apply_cut(Cut::new(source, area, obstacles, self.agent_radius)?);
+        Ok(())
+    }
+
+    /// The mesh without obstacles, and its outline if it was already computed.
+    fn cut_source(&self) -> (Arc<polyanya::Mesh>, Option<Arc<MultiPolygon<f32>>>) {
+        match &self.uncut {
+            Some(uncut) => (uncut.mesh.clone(), Some(uncut.area.clone())),
+            None => (self.mesh.clone(), None),
+        }
+    }
+
+    /// Replaces the mesh by one with obstacles cut out of it. Returns `false`, without changing
+    /// anything, if the cut was computed from another mesh or for another agent radius.
+    pub(crate) fn apply_cut(&mut self, cut: Cut) -> bool {
+        let (source, _) = self.cut_source();
+        if !Arc::ptr_eq(&source, &cut.source) || self.agent_radius != cut.agent_radius {
+            return false;
+        }
+        let uncut = self.uncut.get_or_insert_with(|| Uncut {
+            mesh: cut.source,
+            area: cut.area,
+            links: self.links.clone(),
+            obstacles: MultiPolygon(vec![]),
+            covered: MultiPolygon(vec![]),
+        });
+        uncut.obstacles = cut.obstacles;
+        uncut.covered = cut.covered;
+        let links = uncut.links.clone();
+
+        self.set_mesh(cut.mesh);
+        self.links = links
+            .into_iter()
+            .map(|link| self.out_of_obstacles(link))
+            .collect();
+        self.update_link_islands();
+        true
+    }
+
+    /// Moves the ends of a link that are around an obstacle, but not inside it, to the closest
+    /// point of the mesh.
+    pub(crate) fn out_of_obstacles(&self, link: OffMeshLink) -> OffMeshLink {
+        let Some(uncut) = &self.uncut else {
+            return link;
+        };
+        let moved = |end: Vec2| {
+            let point = Point::new(end.x, end.y);
+            if uncut.covered.intersects(&point) && !uncut.obstacles.intersects(&point) {
+                self.closest_point(end).unwrap_or(end)
+            } else {
+                end
+            }
+        };
+        OffMeshLink {
+            start: moved(link.start),
+            end: moved(link.end),
+            ..link
+        }
+    }
+}
+
+/// A [`Cut`] being computed on the [`AsyncComputeTaskPool`].
+type CutTask = Arc<RwLock<Option<Result<Cut, NavMeshBuildError>>>>;
+
+/// Obstacles being cut out of navmeshes by [`cut_obstacles`].
+#[derive(Default)]
+pub(crate) struct ObstacleCuts {
+    /// Navmesh each obstacle entity is cut from
+    cut_into: HashMap<Entity, AssetId<NavMesh>>,
+    /// Cuts being computed, by navmesh
+    running: HashMap<AssetId<NavMesh>, CutTask>,
+    /// Navmeshes to cut again once their running cut is done, as their obstacles changed
+    pending: HashSet<AssetId<NavMesh>>,
+}
+
+/// Cuts the obstacles out of their navmeshes when they change.
+///
+/// Cuts are computed on the [`AsyncComputeTaskPool`], at most one at a time for each navmesh.
+/// Obstacles changing while a cut is running are cut together once it's done.
+pub(crate) fn cut_obstacles(
+    obstacles: Query<(Entity, Ref<NavMeshObstacle>, Ref<GlobalTransform>)>,
+    mut removed: RemovedComponents<NavMeshObstacle>,
+    mut cuts: Local<ObstacleCuts>,
+    mut asset_events: EventReader<AssetEvent<NavMesh>>,
+    mut navmeshes: ResMut<Assets<NavMesh>>,
+) {
+    let ObstacleCuts {
+        cut_into,
+        running,
+        pending,
+    } = &mut *cuts;
+    let mut to_cut = std::mem::take(pending);
+    for entity in removed.read() {
+        to_cut.extend(cut_into.remove(&entity));
+    }
+    for (entity, obstacle, transform) in &obstacles {
+        if obstacle.is_changed() || transform.is_changed() {
+            let id = obstacle.navmesh.id();
+            to_cut.insert(id);
+            to_cut.extend(cut_into.insert(entity, id));
+        }
+    }
+    // Navmeshes loaded or replaced after their obstacles were added
+    for event in asset_events.read() {
+        match event {
+            AssetEvent::Added { id } => {
+                to_cut.insert(*id);
+            }
+            AssetEvent::Modified { id }
+                if navmeshes
+                    .get(*id)
+                    .is_some_and(|navmesh| navmesh.uncut.is_none()) =>
+            {
+                to_cut.insert(*id);
+            }
+            _ => (),
+        }
+    }
+
+    // Cuts that are done, cut again if the navmesh changed in the meantime
+    running.retain(|id, task| {
+        let Some(cut) = task.write().unwrap().take() else {
+            return true;
+        };
+        match (cut, navmeshes.get_mut(*id)) {
+            (Ok(cut), Some(navmesh)) => {
+                if !navmesh.apply_cut(cut) {
+                    to_cut.insert(*id);
+                }
+            }
+            (Err(error), Some(_)) => warn!("Couldn't cut obstacles from navmesh {id:?}: {error}"),
+            (_, None) => (),
+        }
+        false
+    });
+
+    // Navmeshes with obstacles, or that had obstacles and need to be restored
+    to_cut.retain(|id| {
+        cut_into.values().any(|cut| cut == id)
+            || navmeshes
+                .get(*id)
+                .is_some_and(|navmesh| navmesh.uncut.is_some())
+    });
+
+    for id in to_cut {
+        if running.contains_key(&id) {
+            pending.insert(id);
+            continue;
+        }
+        let Some(navmesh) = navmeshes.get(id) else {
+            continue;
+        };
+        let shapes = obstacles
+            .iter()
+            .filter(|(_, obstacle, _)| obstacle.navmesh.id() == id)
+            .map(|(_, obstacle, transform)| obstacle.shape.to_navmesh(&transform, navmesh))
+            .collect::<Vec<_>>();
+        let (source, area) = navmesh.cut_source();
+        let agent_radius = navmesh.agent_radius;
+        let task = CutTask::default();
+        let writer = task.clone();
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                *writer.write().unwrap() = Some(Cut::new(source, area, &shapes, agent_radius));
+            })
+            .detach();
+        running.insert(id, task);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::{test_fixtures::square_navmesh, VleueNavigatorPlugin};
+
+    #[test]
+    fn obstacles_are_cut_then_restored() {
+        let mut navmesh = square_navmesh();
+        let polygons = navmesh.get().polygons.len();
+
+        navmesh
+            .set_obstacles(&[ObstacleShape::Rectangle(Rectangle::new(2., 2.))
+                .points()
+                .into_iter()
+                .map(|point| point + Vec2::splat(5.))
+                .collect()])
+            .unwrap();
+        assert!(!navmesh.is_in_mesh(Vec2::new(5., 5.)));
+        assert!(navmesh.is_in_mesh(Vec2::new(3., 5.)));
+        assert!(navmesh.path(Vec2::new(3., 5.), Vec2::new(7., 5.)).is_some());
+
+        // The outline of the mesh is kept for the next obstacles
+        let area = navmesh.uncut.as_ref().unwrap().area.clone();
+        navmesh
+            .set_obstacles(&[ObstacleShape::Circle(Circle::new(1.))
+                .points()
+                .into_iter()
+                .map(|point| point + Vec2::splat(2.))
+                .collect()])
+            .unwrap();
+        assert!(Arc::ptr_eq(&navmesh.uncut.as_ref().unwrap().area, &area));
+        assert!(navmesh.is_in_mesh(Vec2::new(5., 5.)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(2., 2.)));
+
+        navmesh.set_obstacles(&[]).unwrap();
+        assert!(navmesh.is_in_mesh(Vec2::new(5., 5.)));
+        assert_eq!(navmesh.get().polygons.len(), polygons);
+    }
+
+    #[test]
+    fn cuts_of_another_mesh_are_not_applied() {
+        let mut navmesh = square_navmesh();
+        let obstacles = [ObstacleShape::Circle(Circle::new(1.))
+            .points()
+            .into_iter()
+            .map(|point| point + Vec2::splat(5.))
+            .collect::<Vec<_>>()];
+        let cut = |navmesh: &NavMesh| {
+            let (source, area) = navmesh.cut_source();
+            Cut::new(source, area, &obstacles, navmesh.agent_radius).unwrap()
+        };
+
+        let stale = cut(&navmesh);
+        navmesh.agent_radius = 0.5;
+        assert!(!navmesh.apply_cut(stale));
+        assert!(navmesh.uncut.is_none());
+        assert!(navmesh.is_in_mesh(Vec2::new(5., 5.)));
+
+        let stale = cut(&navmesh);
+        navmesh = NavMesh {
+            agent_radius: 0.5,
+            ..square_navmesh()
+        };
+        assert!(!navmesh.apply_cut(stale));
+        assert!(navmesh.apply_cut(cut(&navmesh)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(5., 5.)));
+    }
+
+    #[test]
+    fn links_are_moved_out_of_obstacles() {
+        let mut navmesh = NavMesh {
+            agent_radius: 0.5,
+            ..square_navmesh()
+        };
+        let jump = OffMeshLink {
+            start: Vec2::new(1., 5.),
+            end: Vec2::new(5.2, 5.),
+            bidirectional: true,
+            cost: 1.,
+            tag: 0,
+        };
+        let ladder = OffMeshLink {
+            start: Vec2::new(1., 9.),
+            end: Vec2::new(6.8, 9.),
+            ..jump
+        };
+        navmesh.add_link(jump);
+        navmesh.add_link(ladder);
+        let obstacle = |center: Vec2| {
+            ObstacleShape::Rectangle(Rectangle::new(2., 2.))
+                .points()
+                .into_iter()
+                .map(|point| point + center)
+                .collect::<Vec<_>>()
+        };
+
+        navmesh
+            .set_obstacles(&[obstacle(Vec2::new(5., 5.)), obstacle(Vec2::new(8., 9.))])
+            .unwrap();
+        // The end of the ladder is next to an obstacle, and moved to its side
+        let moved = navmesh.links()[1].end;
+        assert!(moved.distance(Vec2::new(6.5, 9.)) < 0.01, "{moved}");
+        assert_eq!(navmesh.link_islands[1], [Some(0), Some(0)]);
+        // The end of the jump is in an obstacle, it can't be taken
+        assert_eq!(navmesh.links()[0], jump);
+        assert_eq!(navmesh.link_islands[0], [Some(0), None]);
+
+        // Links are restored with the mesh, and links changed later are moved too
+        navmesh.set_obstacles(&[]).unwrap();
+        assert_eq!(navmesh.links(), &[jump, ladder]);
+        assert_eq!(navmesh.link_islands[0], [Some(0), Some(0)]);
+        navmesh
+            .set_obstacles(&[obstacle(Vec2::new(8., 9.))])
+            .unwrap();
+        navmesh.set_link(
+            0,
+            OffMeshLink {
+                end: Vec2::new(6.7, 8.5),
+                ..jump
+            },
+        );
+        let moved = navmesh.links()[0].end;
+        assert!(moved.distance(Vec2::new(6.5, 8.5)) < 0.01, "{moved}");
+    }
+
+    #[test]
+    fn obstacle_entities_cut_the_navmesh() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), VleueNavigatorPlugin));
+        let navmesh = app.world.resource_mut::<Assets<NavMesh>>().reserve_handle();
+        let obstacle = app
+            .world
+            .spawn((
+                NavMeshObstacle {
+                    shape: ObstacleShape::Circle(Circle::new(1.)),
+                    navmesh: navmesh.clone(),
+                },
+                GlobalTransform::from_translation(Vec3::new(5., 5., 0.)),
+            ))
+            .id();
+        app.update();
+        app.world
+            .resource_mut::<Assets<NavMesh>>()
+            .insert(&navmesh, square_navmesh());
+
+        let is_in_mesh = |app: &App, point: Vec2| {
+            app.world
+                .resource::<Assets<NavMesh>>()
+                .get(&navmesh)
+                .unwrap()
+                .is_in_mesh(point)
+        };
+        // Cuts are done on another thread, and applied a few frames later
+        let wait_for = |app: &mut App, done: &dyn Fn(&App) -> bool| {
+            for _ in 0..100 {
+                app.update();
+                if done(app) {
+                    return;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            }
+            panic!("obstacles were not cut");
+        };
+        wait_for(&mut app, &|app| !is_in_mesh(app, Vec2::new(5., 5.)));
+
+        // Moves while the navmesh is being cut are cut together afterwards
+        for x in [8., 2.] {
+            app.world
+                .entity_mut(obstacle)
+                .insert(GlobalTransform::from_translation(Vec3::new(x, 2., 0.)));
+            app.update();
+        }
+        wait_for(&mut app, &|app| {
+            is_in_mesh(app, Vec2::new(5., 5.))
+                && is_in_mesh(app, Vec2::new(8., 2.))
+                && !is_in_mesh(app, Vec2::new(2., 2.))
+        });
+
+        app.world.despawn(obstacle);
+        wait_for(&mut app, &|app| is_in_mesh(app, Vec2::new(2., 2.)));
+    }
+}
@@ -0,0 +1,181 @@
+//! Components to request paths from the ECS, resolved asynchronously by [`VleueNavigatorPlugin`](crate::VleueNavigatorPlugin)
+
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use bevy::{prelude::*, tasks::AsyncComputeTaskPool, utils::Instant};
+
+use crate::{NavMesh, TransformedPath};
+
+/// Request a path between two points on a [`NavMesh`].
+///
+/// Points are in world coordinates, and are transformed using [`NavMesh::transform`].
+///
+/// Once the path has been computed, this component is removed and replaced by either
+/// [`ComputedPath`] or [`PathFailed`]. Inserting a new [`PathRequest`] while a path is
+/// being computed will discard the previous computation, as will a modification of the
+/// [`NavMesh`] asset.
+#[derive(Component, Debug, Clone)]
+pub struct PathRequest {
+    /// Starting point of the path.
+    pub from: Vec3,
+    /// Destination of the path.
+    pub to: Vec3,
+    /// [`NavMesh`] to use for the search. The request waits until it is loaded.
+    pub navmesh: Handle<NavMesh>,
+}
+
+/// A path that was computed following a [`PathRequest`].
+#[derive(Component, Debug, Clone, PartialEq)]
+pub struct ComputedPath(pub TransformedPath);
+
+/// Marker added when no path could be found following a [`PathRequest`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PathFailed;
+
+/// How long resolving a [`PathRequest`] took, added with [`ComputedPath`] or [`PathFailed`].
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct PathTiming {
+    /// Time between the request being picked up and the search starting on the
+    /// [`AsyncComputeTaskPool`].
+    pub delay: Duration,
+    /// Time spent searching for the path.
+    pub duration: Duration,
+}
+
+/// How paths requested with [`PathRequest`] are searched on the [`AsyncComputeTaskPool`].
+///
+/// This resource is added by [`VleueNavigatorPlugin`](crate::VleueNavigatorPlugin), and can be
+/// changed at any time, applying to the next requests.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PathSearchMode {
+    /// The search yields regularly to the other tasks of the pool, see
+    /// [`NavMesh::get_transformed_path`].
+    #[default]
+    Async,
+    /// The search runs without yielding once started, see [`NavMesh::transformed_path`].
+    Blocking,
+}
+
+/// A path being computed on the [`AsyncComputeTaskPool`] following a [`PathRequest`].
+#[derive(Component, Debug)]
+pub struct FindingPath(Arc<RwLock<Option<SearchResult>>>);
+
+/// The path found by a search, if any, and how long it took
+type SearchResult = (Option<TransformedPath>, PathTiming);
+
+pub(crate) fn compute_paths(
+    mut commands: Commands,
+    requests: Query<(Entity, Ref<PathRequest>, Has<FindingPath>)>,
+    navmeshes: Res<Assets<NavMesh>>,
+    mut asset_events: EventReader<AssetEvent<NavMesh>>,
+    mode: Res<PathSearchMode>,
+) {
+    let modified = asset_events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    for (entity, request, finding) in &requests {
+        if finding && !request.is_changed() && !modified.contains(&request.navmesh.id()) {
+            continue;
+        }
+        let Some(navmesh) = navmeshes.get(&request.navmesh) else {
+            continue;
+        };
+
+        let navmesh = navmesh.clone();
+        let (from, to) = (request.from, request.to);
+        let finding = FindingPath(Arc::new(RwLock::new(None)));
+        let writer = finding.0.clone();
+        let mode = *mode;
+        let requested = Instant::now();
+        AsyncComputeTaskPool::get()
+            .spawn(async move {
+                let started = Instant::now();
+                let path = match mode {
+                    PathSearchMode::Async => navmesh.get_transformed_path(from, to).await,
+                    PathSearchMode::Blocking => navmesh.transformed_path(from, to),
+                };
+                let timing = PathTiming {
+                    delay: started - requested,
+                    duration: started.elapsed(),
+                };
+                *writer.write().unwrap() = Some((path, timing));
+            })
+            .detach();
+        commands.entity(entity).insert(finding);
+    }
+}
+
+pub(crate) fn poll_path_tasks(mut commands: Commands, computing: Query<(Entity, &FindingPath)>) {
+    for (entity, task) in &computing {
+        let Some((path, timing)) = task.0.write().unwrap().take() else {
+            continue;
+        };
+        let mut entity = commands.entity(entity);
+        entity.remove::<(FindingPath, PathRequest)>().insert(timing);
+        if let Some(path) = path {
+            entity.remove::<PathFailed>().insert(ComputedPath(path));
+        } else {
+            entity.remove::<ComputedPath>().insert(PathFailed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::asset::AssetPlugin;
+
+    use super::*;
+    use crate::{test_fixtures::square_navmesh, VleueNavigatorPlugin};
+
+    fn run_request(from: Vec3, to: Vec3, mode: PathSearchMode) -> (Option<ComputedPath>, bool) {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), VleueNavigatorPlugin))
+            .insert_resource(mode);
+        let navmesh = app
+            .world
+            .resource_mut::<Assets<NavMesh>>()
+            .add(square_navmesh());
+        let entity = app.world.spawn(PathRequest { from, to, navmesh }).id();
+
+        for _ in 0..100 {
+            app.update();
+            if !app.world.entity(entity).contains::<PathRequest>() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        let entity = app.world.entity(entity);
+        assert!(entity.contains::<PathTiming>());
+        (
+            entity.get::<ComputedPath>().cloned(),
+            entity.contains::<PathFailed>(),
+        )
+    }
+
+    #[test]
+    fn request_is_resolved_with_computed_path() {
+        for mode in [PathSearchMode::Async, PathSearchMode::Blocking] {
+            let (path, failed) = run_request(Vec3::new(1., 1., 0.), Vec3::new(9., 9., 0.), mode);
+            assert!(!failed);
+            assert_eq!(path.unwrap().0.path, vec![Vec3::new(9., 9., 0.)]);
+        }
+    }
+
+    #[test]
+    fn request_outside_mesh_fails() {
+        let (path, failed) = run_request(
+            Vec3::new(1., 1., 0.),
+            Vec3::new(20., 20., 0.),
+            PathSearchMode::Async,
+        );
+        assert!(failed);
+        assert!(path.is_none());
+    }
+}
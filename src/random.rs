@@ -0,0 +1,316 @@
+//! Random points on a [`NavMesh`], to pick wandering targets or spawn positions
+
+use bevy::prelude::*;
+use itertools::Itertools;
+use rand::Rng;
+
+use crate::NavMesh;
+
+/// Triangles of a mesh with their cumulative area, kept to pick random points over the whole
+/// mesh without splitting its polygons again.
+#[derive(Debug, Default)]
+pub(crate) struct AreaTriangles {
+    triangles: Vec<[Vec2; 3]>,
+    /// Area of the triangles up to each one, included
+    cumulative: Vec<f32>,
+}
+
+impl AreaTriangles {
+    pub(crate) fn new(mesh: &polyanya::Mesh) -> Self {
+        let triangles = mesh
+            .polygons
+            .iter()
+            .map(|polygon| {
+                polygon
+                    .vertices
+                    .iter()
+                    .map(|vertex| mesh.vertices[*vertex as usize].coords)
+                    .collect()
+            })
+            .flat_map(fan)
+            .collect::<Vec<_>>();
+        let cumulative = triangles
+            .iter()
+            .scan(0.0, |total, triangle| {
+                *total += area(triangle);
+                Some(*total)
+            })
+            .collect();
+        AreaTriangles {
+            triangles,
+            cumulative,
+        }
+    }
+
+    /// A random point uniformly distributed over the triangles.
+    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec2> {
+        let total = self.cumulative.last().copied().unwrap_or_default();
+        if total <= 0.0 {
+            return None;
+        }
+        let picked = rng.gen_range(0.0..total);
+        let index = self
+            .cumulative
+            .partition_point(|area| *area <= picked)
+            .min(self.triangles.len() - 1);
+        Some(sample_triangle(self.triangles[index], rng))
+    }
+}
+
+/// Number of points tried by [`NavMesh::random_point_within`] before giving up.
+const MAX_ATTEMPTS: usize = 32;
+
+impl NavMesh {
+    /// A random point of the mesh, uniformly distributed over its area.
+    ///
+    /// Returns `None` only if the mesh is empty.
+    pub fn random_point<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec2> {
+        self.area_triangles.sample(rng)
+    }
+
+    /// A random point of the mesh, uniformly distributed over its area, using the
+    /// [`NavMesh::transform`].
+    ///
+    /// The point is placed on the surface of the mesh.
+    pub fn transformed_random_point<R: Rng + ?Sized>(&self, rng: &mut R) -> Option<Vec3> {
+        self.random_point(rng).map(|point| self.to_surface(point))
+    }
+
+    /// A random point of the mesh at most `radius` away from `center`, uniformly distributed
+    /// over the part of the mesh in that circle.
+    ///
+    /// Returns `None` if the circle doesn't overlap the mesh. This can also happen, rarely, when
+    /// only a tiny part of the circle is on the mesh.
+    pub fn random_point_within<R: Rng + ?Sized>(
+        &self,
+        center: Vec2,
+        radius: f32,
+        rng: &mut R,
+    ) -> Option<Vec2> {
+        self.random_point_in_polygons(center, radius, |_| true, rng)
+    }
+
+    /// A random point at most `radius` away from `center`, like
+    /// [`NavMesh::random_point_within`], that can be reached from `center` by walking on the
+    /// mesh.
+    ///
+    /// Points are on the island of `center` or on an island reached from it through off-mesh
+    /// links, like for [`NavMesh::is_reachable`]. Area types are ignored. Returns `None` if
+    /// `center` is not in the mesh.
+    pub fn random_reachable_point_within<R: Rng + ?Sized>(
+        &self,
+        center: Vec2,
+        radius: f32,
+        rng: &mut R,
+    ) -> Option<Vec2> {
+        let island = self.island_of(center)?;
+        self.random_point_in_polygons(
+            center,
+            radius,
+            |polygon| self.is_island_reachable(island, self.islands[polygon]),
+            rng,
+        )
+    }
+
+    /// Samples the polygons kept by `filter`, clipped to the square around the circle, until a
+    /// point is in the circle.
+    ///
+    /// Only the polygons around the circle are found, in the spatial index of the mesh.
+    fn random_point_in_polygons<R: Rng + ?Sized>(
+        &self,
+        center: Vec2,
+        radius: f32,
+        filter: impl Fn(usize) -> bool,
+        rng: &mut R,
+    ) -> Option<Vec2> {
+        let (min, max) = (center - Vec2::splat(radius), center + Vec2::splat(radius));
+        let triangles = self
+            .polygon_index
+            .within(min, max)
+            .sorted_unstable()
+            .dedup()
+            .filter(|polygon| filter(*polygon))
+            .map(|polygon| {
+                let points = self.mesh.polygons[polygon]
+                    .vertices
+                    .iter()
+                    .map(|vertex| self.mesh.vertices[*vertex as usize].coords)
+                    .collect();
+                clip(points, min, max)
+            })
+            .flat_map(fan)
+            .collect::<Vec<_>>();
+        (0..MAX_ATTEMPTS)
+            .filter_map(|_| sample(&triangles, rng))
+            .find(|point| point.distance_squared(center) <= radius * radius)
+    }
+}
+
+/// Splits a convex polygon into triangles sharing its first point.
+fn fan(points: Vec<Vec2>) -> Vec<[Vec2; 3]> {
+    (1..points.len().saturating_sub(1))
+        .map(|i| [points[0], points[i], points[i + 1]])
+        .collect()
+}
+
+/// Clips a convex polygon to the box between `min` and `max`.
+fn clip(mut points: Vec<Vec2>, min: Vec2, max: Vec2) -> Vec<Vec2> {
+    // Each side of the box, as a point on it and the direction towards the inside
+    for (on, inside) in [
+        (min, Vec2::X),
+        (min, Vec2::Y),
+        (max, Vec2::NEG_X),
+        (max, Vec2::NEG_Y),
+    ] {
+        let distance = |point: Vec2| (point - on).dot(inside);
+        points = points
+            .iter()
+            .circular_tuple_windows()
+            .flat_map(|(a, b)| {
+                let (da, db) = (distance(*a), distance(*b));
+                let crossing = (da * db < 0.0).then(|| a.lerp(*b, da / (da - db)));
+                (da >= 0.0).then_some(*a).into_iter().chain(crossing)
+            })
+            .collect();
+    }
+    points
+}
+
+/// A random point uniformly distributed over the triangles.
+fn sample<R: Rng + ?Sized>(triangles: &[[Vec2; 3]], rng: &mut R) -> Option<Vec2> {
+    let areas = triangles.iter().map(area).collect::<Vec<_>>();
+    let total: f32 = areas.iter().sum();
+    if total <= 0.0 {
+        return None;
+    }
+    let mut picked = rng.gen_range(0.0..total);
+    let triangle = triangles
+        .iter()
+        .zip(&areas)
+        .find(|(_, area)| {
+            picked -= **area;
+            picked < 0.0
+        })
+        .map_or(triangles[triangles.len() - 1], |(triangle, _)| *triangle);
+    Some(sample_triangle(triangle, rng))
+}
+
+/// Area of a triangle.
+fn area([a, b, c]: &[Vec2; 3]) -> f32 {
+    (*b - *a).perp_dot(*c - *a).abs() / 2.0
+}
+
+/// A random point uniformly distributed in a triangle.
+fn sample_triangle<R: Rng + ?Sized>([a, b, c]: [Vec2; 3], rng: &mut R) -> Vec2 {
+    let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+    if u + v > 1.0 {
+        (u, v) = (1.0 - u, 1.0 - v);
+    }
+    a + (b - a) * u + (c - a) * v
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::links::OffMeshLink;
+
+    /// Two squares that are not connected, the second one being three times as large
+    fn two_islands() -> NavMesh {
+        NavMesh::from_polyanya_mesh(
+            polyanya::Trimesh {
+                vertices: vec![
+                    Vec2::new(0., 0.),
+                    Vec2::new(2., 0.),
+                    Vec2::new(2., 2.),
+                    Vec2::new(0., 2.),
+                    Vec2::new(3., 0.),
+                    Vec2::new(9., 0.),
+                    Vec2::new(9., 2.),
+                    Vec2::new(3., 2.),
+                ],
+                triangles: vec![[0, 1, 2], [0, 2, 3], [4, 5, 6], [4, 6, 7]],
+            }
+            .into(),
+        )
+    }
+
+    #[test]
+    fn random_points_are_uniform_on_the_mesh() {
+        let navmesh = two_islands();
+        let mut rng = StdRng::seed_from_u64(4);
+
+        let points = (0..1000)
+            .map(|_| navmesh.random_point(&mut rng).unwrap())
+            .collect::<Vec<_>>();
+        assert!(points.iter().all(|point| navmesh.is_in_mesh(*point)));
+        let on_first = points.iter().filter(|point| point.x < 2.5).count();
+        assert!((200..300).contains(&on_first), "{on_first}");
+    }
+
+    #[test]
+    fn random_points_within_circle() {
+        let navmesh = two_islands();
+        let mut rng = StdRng::seed_from_u64(4);
+        let center = Vec2::new(1.5, 1.);
+
+        for _ in 0..100 {
+            let point = navmesh.random_point_within(center, 3., &mut rng).unwrap();
+            assert!(navmesh.is_in_mesh(point));
+            assert!(point.distance(center) <= 3.);
+        }
+        let reached_second = (0..100)
+            .map(|_| navmesh.random_point_within(center, 3., &mut rng).unwrap())
+            .any(|point| point.x > 2.5);
+        assert!(reached_second);
+
+        for _ in 0..100 {
+            let point = navmesh
+                .random_reachable_point_within(center, 3., &mut rng)
+                .unwrap();
+            assert!(point.x <= 2.);
+        }
+        assert_eq!(
+            navmesh.random_point_within(Vec2::new(20., 20.), 3., &mut rng),
+            None
+        );
+        assert_eq!(
+            navmesh.random_reachable_point_within(Vec2::new(20., 20.), 30., &mut rng),
+            None
+        );
+    }
+
+    #[test]
+    fn random_reachable_points_follow_links() {
+        let mut navmesh = two_islands();
+        let mut rng = StdRng::seed_from_u64(4);
+        let (first, second) = (Vec2::new(1.5, 1.), Vec2::new(4., 1.));
+        navmesh.add_link(OffMeshLink {
+            start: first,
+            end: second,
+            bidirectional: false,
+            cost: 1.,
+            tag: 0,
+        });
+
+        let points = (0..100)
+            .map(|_| {
+                navmesh
+                    .random_reachable_point_within(first, 3., &mut rng)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(points.iter().any(|point| point.x > 2.5));
+        assert!(points
+            .iter()
+            .all(|point| navmesh.is_reachable(first, *point)));
+        // The link only goes one way
+        for _ in 0..100 {
+            let point = navmesh
+                .random_reachable_point_within(second, 3., &mut rng)
+                .unwrap();
+            assert!(point.x >= 3.);
+        }
+    }
+}
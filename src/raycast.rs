@@ -0,0 +1,258 @@
+//! Straight line queries on a [`NavMesh`], to check if an agent can walk directly between two points
+
+use bevy::prelude::*;
+use itertools::Itertools;
+
+use crate::{islands::neighbour, NavMesh};
+
+/// Where a ray leaves the [`NavMesh`], see [`NavMesh::raycast`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RaycastHit {
+    /// Point where the ray is blocked, in mesh coordinates.
+    pub point: Vec2,
+    /// Normal of the blocking edge, pointing back into the mesh.
+    pub normal: Vec2,
+}
+
+/// Where a ray leaves the [`NavMesh`], see [`NavMesh::transformed_raycast`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TransformedRaycastHit {
+    /// Point where the ray is blocked, on the surface of the mesh.
+    pub point: Vec3,
+    /// Normal of the blocking edge, pointing back into the mesh.
+    pub normal: Vec3,
+}
+
+/// Distance to an edge line, scaled by the edge length, under which polyanya considers a point
+/// on the edge.
+const POINT_LOCATION_EPSILON: f32 = 1e-4;
+
+impl NavMesh {
+    /// Index of the polygon containing `point`.
+    ///
+    /// This matches how polyanya locates points: points on an edge are in the polygon, and points
+    /// outside of the mesh by less than its delta are in a polygon a delta away.
+    pub(crate) fn polygon_at(&self, point: Vec2) -> Option<usize> {
+        let delta = self.mesh.delta();
+        [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(delta, 0.0),
+            Vec2::new(delta, delta),
+            Vec2::new(0.0, delta),
+            Vec2::new(-delta, delta),
+            Vec2::new(-delta, 0.0),
+            Vec2::new(-delta, -delta),
+            Vec2::new(0.0, -delta),
+            Vec2::new(delta, -delta),
+        ]
+        .into_iter()
+        .find_map(|offset| {
+            let point = point + offset;
+            self.polygon_index
+                .within(point, point)
+                .filter(|index| self.is_in_polygon(point, *index))
+                .min()
+        })
+    }
+
+    /// Checks if `point` is in a polygon, with the same tolerance as polyanya.
+    fn is_in_polygon(&self, point: Vec2, polygon: usize) -> bool {
+        let polygon = &self.mesh.polygons[polygon];
+        if polygon.vertices.is_empty() {
+            return false;
+        }
+        for (a, b) in polygon
+            .vertices
+            .iter()
+            .map(|vertex| self.mesh.vertices[*vertex as usize].coords)
+            .circular_tuple_windows()
+        {
+            let side = (b - a).perp_dot(point - a);
+            if side.abs() < POINT_LOCATION_EPSILON {
+                // On the line of the edge, only inside if it's on the edge itself
+                return point.cmpge(a.min(b)).all() && point.cmple(a.max(b)).all();
+            }
+            if side < 0.0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks if the straight line from `from` to `to` stays in the mesh.
+    ///
+    /// Returns `None` if it does, otherwise the first point where it leaves the mesh. This walks
+    /// through the polygons crossed by the line, which is much cheaper than a path search.
+    ///
+    /// If `from` is not in the mesh, the ray is blocked right away with a zero normal.
+    /// [`NavMesh::is_in_mesh`] and this method agree on which points are in the mesh.
+    pub fn raycast(&self, from: Vec2, to: Vec2) -> Option<RaycastHit> {
+        let Some(mut current) = self.polygon_at(from) else {
+            return Some(RaycastHit {
+                point: from,
+                normal: Vec2::ZERO,
+            });
+        };
+        let direction = to - from;
+        let mut reached = 0.0;
+
+        for _ in 0..self.mesh.polygons.len() {
+            let polygon = &self.mesh.polygons[current];
+            // Edge through which the line leaves the polygon: the first one crossed going outward.
+            // There is none when the ray has no length.
+            let (t, a, b) = polygon
+                .vertices
+                .iter()
+                .circular_tuple_windows()
+                .filter_map(|(a, b)| {
+                    let (start, end) = (
+                        self.mesh.vertices[*a as usize].coords,
+                        self.mesh.vertices[*b as usize].coords,
+                    );
+                    let edge = end - start;
+                    let outward = edge.perp_dot(direction);
+                    (outward < 0.0).then(|| (edge.perp_dot(start - from) / outward, *a, *b))
+                })
+                .min_by(|(a, _, _), (b, _, _)| a.total_cmp(b))
+                .unwrap_or((f32::INFINITY, 0, 0));
+            if t >= 1.0 {
+                return None;
+            }
+            reached = t.max(reached);
+
+            match neighbour(&self.mesh, current, a, b) {
+                Some(next) => current = next,
+                None => {
+                    let edge = self.mesh.vertices[b as usize].coords
+                        - self.mesh.vertices[a as usize].coords;
+                    return Some(RaycastHit {
+                        point: from + direction * reached,
+                        normal: edge.perp().normalize_or_zero(),
+                    });
+                }
+            }
+        }
+        // Each polygon is crossed at most once by a straight line, going around more of them can
+        // only come from rounding errors: consider the ray blocked where it was
+        Some(RaycastHit {
+            point: from + direction * reached,
+            normal: Vec2::ZERO,
+        })
+    }
+
+    /// Checks if the straight line from `from` to `to` stays in the mesh, using the
+    /// [`NavMesh::transform`].
+    ///
+    /// See [`NavMesh::raycast`].
+    pub fn transformed_raycast(&self, from: Vec3, to: Vec3) -> Option<TransformedRaycastHit> {
+        let hit = self.raycast(
+            self.transform.transform_point(from).xy(),
+            self.transform.transform_point(to).xy(),
+        )?;
+        Some(TransformedRaycastHit {
+            point: self.to_surface(hit.point),
+            normal: self
+                .inverse_transform()
+                .transform_vector3(hit.normal.extend(0.0))
+                .normalize_or_zero(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::*;
+    use crate::test_fixtures::square_with_obstacle;
+
+    /// A square room with a pillar in the middle
+    fn room() -> NavMesh {
+        square_with_obstacle(Vec2::new(4., 4.), Vec2::new(6., 6.))
+    }
+
+    #[test]
+    fn raycast_stops_at_first_blocking_edge() {
+        let navmesh = room();
+
+        assert_eq!(navmesh.raycast(Vec2::new(1., 1.), Vec2::new(9., 2.)), None);
+        assert_eq!(navmesh.raycast(Vec2::new(1., 9.), Vec2::new(9., 9.5)), None);
+        assert_eq!(navmesh.raycast(Vec2::new(2., 2.), Vec2::new(2., 2.)), None);
+
+        let hit = navmesh
+            .raycast(Vec2::new(1., 5.), Vec2::new(9., 5.))
+            .unwrap();
+        assert!(hit.point.distance(Vec2::new(4., 5.)) < 1e-4, "{hit:?}");
+        assert!(hit.normal.distance(Vec2::new(-1., 0.)) < 1e-4, "{hit:?}");
+
+        let hit = navmesh
+            .raycast(Vec2::new(8., 2.), Vec2::new(8., 12.))
+            .unwrap();
+        assert!(hit.point.distance(Vec2::new(8., 10.)) < 1e-4, "{hit:?}");
+        assert!(hit.normal.distance(Vec2::new(0., -1.)) < 1e-4, "{hit:?}");
+
+        let hit = navmesh
+            .raycast(Vec2::new(-1., 2.), Vec2::new(2., 2.))
+            .unwrap();
+        assert_eq!(hit.normal, Vec2::ZERO);
+    }
+
+    #[test]
+    fn points_are_located_like_polyanya() {
+        let navmesh = room();
+        let delta = navmesh.mesh.delta();
+
+        for point in [
+            Vec2::new(2., 2.),
+            Vec2::new(0., 5.),
+            Vec2::new(-delta / 2., 5.),
+            Vec2::new(-delta * 2., 5.),
+            Vec2::new(4., 5.),
+            Vec2::new(4. + delta / 2., 5.),
+            Vec2::new(5., 5.),
+            Vec2::new(10., 10.),
+            Vec2::new(10. + delta / 2., 10. + delta / 2.),
+            Vec2::new(11., 10.),
+        ] {
+            assert_eq!(
+                navmesh.polygon_at(point).is_some(),
+                navmesh.mesh.point_in_mesh(point),
+                "{point:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn raycast_through_vertices() {
+        let navmesh = room();
+
+        // Along the diagonal, through the vertices shared by many triangles
+        assert_eq!(navmesh.raycast(Vec2::new(0., 10.), Vec2::new(4., 6.)), None);
+        let hit = navmesh
+            .raycast(Vec2::new(0., 0.), Vec2::new(10., 10.))
+            .unwrap();
+        assert!(hit.point.distance(Vec2::new(4., 4.)) < 1e-4, "{hit:?}");
+        let hit = navmesh
+            .raycast(Vec2::new(2., 2.), Vec2::new(12., 12.))
+            .unwrap();
+        assert!(hit.point.distance(Vec2::new(4., 4.)) < 1e-4, "{hit:?}");
+    }
+
+    #[test]
+    fn transformed_raycast() {
+        let mut navmesh = room();
+        navmesh.set_transform(Transform::from_rotation(Quat::from_rotation_x(FRAC_PI_2)));
+
+        let hit = navmesh
+            .transformed_raycast(Vec3::new(1., 0., -5.), Vec3::new(9., 0., -5.))
+            .unwrap();
+        assert!(hit.point.distance(Vec3::new(4., 0., -5.)) < 1e-4, "{hit:?}");
+        assert!(
+            hit.normal.distance(Vec3::new(-1., 0., 0.)) < 1e-4,
+            "{hit:?}"
+        );
+        assert!(navmesh
+            .transformed_raycast(Vec3::new(1., 0., -1.), Vec3::new(9., 0., -2.))
+            .is_none());
+    }
+}
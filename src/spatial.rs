@@ -0,0 +1,128 @@
+//! Grid of bounding boxes, to find the polygons or triangles around a point without going through all of them
+
+use bevy::{math::IVec2, prelude::*, utils::HashMap};
+use itertools::Either;
+
+/// Maximum number of cells an item can cover. Larger items are kept apart and checked for every
+/// query, so that a few very large items among many small ones don't fill the grid.
+const MAX_CELLS_PER_ITEM: i64 = 64;
+
+/// Items indexed by their bounding box in a grid with cells about the size of the median item.
+#[derive(Debug, Default)]
+pub(crate) struct SpatialIndex {
+    cell_size: f32,
+    cells: HashMap<IVec2, Vec<usize>>,
+    /// Items covering more than [`MAX_CELLS_PER_ITEM`] cells
+    large: Vec<usize>,
+}
+
+impl SpatialIndex {
+    /// Indexes items by their bounding box, as the minimum and maximum corners.
+    pub(crate) fn new(bounds: &[(Vec2, Vec2)]) -> Self {
+        // The median is not skewed by a few very large items
+        let mut sizes = bounds
+            .iter()
+            .map(|(min, max)| (*max - *min).max_element())
+            .filter(|size| size.is_finite())
+            .collect::<Vec<_>>();
+        sizes.sort_unstable_by(f32::total_cmp);
+        let cell_size = sizes
+            .get(sizes.len() / 2)
+            .copied()
+            .unwrap_or_default()
+            .max(f32::EPSILON);
+
+        let mut index = SpatialIndex {
+            cell_size,
+            ..default()
+        };
+        for (item, (min, max)) in bounds.iter().enumerate() {
+            let (min, max) = (index.cell(*min), index.cell(*max));
+            let covered =
+                (max.x as i64 - min.x as i64 + 1).saturating_mul(max.y as i64 - min.y as i64 + 1);
+            if covered > MAX_CELLS_PER_ITEM {
+                index.large.push(item);
+                continue;
+            }
+            for x in min.x..=max.x {
+                for y in min.y..=max.y {
+                    index.cells.entry(IVec2::new(x, y)).or_default().push(item);
+                }
+            }
+        }
+        index
+    }
+
+    fn cell(&self, point: Vec2) -> IVec2 {
+        (point / self.cell_size).floor().as_ivec2()
+    }
+
+    /// Items whose bounding box may intersect the box from `min` to `max`. An item can be listed
+    /// more than once.
+    pub(crate) fn within(&self, min: Vec2, max: Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (min, max) = (self.cell(min), self.cell(max));
+        let covered =
+            (max.x as i64 - min.x as i64 + 1).saturating_mul(max.y as i64 - min.y as i64 + 1);
+        // A box larger than the grid goes through the cells of the grid instead
+        let cells = if covered > self.cells.len() as i64 {
+            Either::Left(
+                self.cells
+                    .iter()
+                    .filter(move |(cell, _)| cell.cmpge(min).all() && cell.cmple(max).all())
+                    .map(|(_, items)| items),
+            )
+        } else {
+            Either::Right(
+                (min.x..=max.x)
+                    .flat_map(move |x| (min.y..=max.y).map(move |y| IVec2::new(x, y)))
+                    .filter_map(|cell| self.cells.get(&cell)),
+            )
+        };
+        cells.flatten().chain(&self.large).copied()
+    }
+}
+
+/// Bounding box of each polygon of a mesh.
+pub(crate) fn polygon_bounds(mesh: &polyanya::Mesh) -> Vec<(Vec2, Vec2)> {
+    mesh.polygons
+        .iter()
+        .map(|polygon| {
+            polygon
+                .vertices
+                .iter()
+                .map(|vertex| mesh.vertices[*vertex as usize].coords)
+                .fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), point| {
+                    (min.min(point), max.max(point))
+                })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_items_dont_fill_the_grid() {
+        let mut bounds = (0..100)
+            .map(|i| {
+                let min = Vec2::new(i as f32, 0.);
+                (min, min + Vec2::ONE)
+            })
+            .collect::<Vec<_>>();
+        bounds.push((Vec2::splat(-1e6), Vec2::splat(1e6)));
+        let index = SpatialIndex::new(&bounds);
+
+        assert_eq!(index.large, vec![100]);
+        assert!(index.cells.len() < 1000);
+        let within = index
+            .within(Vec2::new(20.5, 0.5), Vec2::new(22.5, 0.5))
+            .collect::<Vec<_>>();
+        assert!([20, 21, 22, 100].iter().all(|item| within.contains(item)));
+        assert!(!within.contains(&30));
+        let everything = index
+            .within(Vec2::splat(f32::MIN), Vec2::splat(f32::MAX))
+            .collect::<Vec<_>>();
+        assert!((0..=100).all(|item| everything.contains(&item)));
+    }
+}
@@ -0,0 +1,476 @@
+//! Build a [`NavMesh`] from the shapes of an SVG file, to sketch levels in vector tools
+
+use std::{error::Error, fmt::Display};
+
+use bevy::{math::Affine2, prelude::*, utils::HashMap};
+use geo::{BooleanOps, MultiPolygon};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+use svgtypes::{PointsParser, SimplePathSegment, SimplifyingPathParser, ViewBox};
+
+use crate::{
+    builder::{to_polygon, triangulate, union_all},
+    NavMesh, NavMeshBuildError,
+};
+
+/// Elements whose content is not drawn directly.
+const NOT_RENDERED: [&str; 6] = ["defs", "clipPath", "mask", "marker", "pattern", "symbol"];
+
+/// Settings for [`NavMesh::from_svg`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SvgSettings {
+    /// Class of the shapes that are obstacles. Default is `obstacle`.
+    pub obstacle_class: String,
+    /// Size of a unit of the SVG file in mesh coordinates. Default is `1.0`.
+    pub scale: f32,
+    /// Maximum distance between a curve and the segments replacing it, in mesh coordinates.
+    /// Default is `0.1`.
+    pub tolerance: f32,
+}
+
+impl Default for SvgSettings {
+    fn default() -> Self {
+        Self {
+            obstacle_class: "obstacle".to_string(),
+            scale: 1.0,
+            tolerance: 0.1,
+        }
+    }
+}
+
+/// Error that can happen while reading an SVG file
+#[derive(Debug)]
+pub enum SvgError {
+    /// The file is not a valid XML file
+    Xml(roxmltree::Error),
+    /// An attribute has an invalid value
+    InvalidAttribute(String),
+    /// Error when building the `NavMesh` from the shapes
+    Build(NavMeshBuildError),
+}
+
+impl Display for SvgError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SvgError::Xml(xml_error) => write!(f, "XML error: {}", xml_error),
+            SvgError::InvalidAttribute(reason) => write!(f, "invalid attribute: {}", reason),
+            SvgError::Build(build_error) => {
+                write!(f, "NavMesh build error: {}", build_error)
+            }
+        }
+    }
+}
+
+impl Error for SvgError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SvgError::Xml(xml_error) => Some(xml_error),
+            SvgError::InvalidAttribute(_) => None,
+            SvgError::Build(build_error) => Some(build_error),
+        }
+    }
+}
+
+impl NavMesh {
+    /// Creates a [`NavMesh`] from the `<path>` and `<polygon>` elements of an SVG file.
+    ///
+    /// Filled shapes are walkable, except those with the [`SvgSettings::obstacle_class`] class
+    /// (or in a group with it) which are cut out of the walkable area. Shapes with their fill set
+    /// to `none`, or that are not displayed, are ignored. Curves are replaced by segments.
+    ///
+    /// Rings of a shape are combined following its `fill-rule`: with the default `nonzero`, inner
+    /// rings are holes when they turn the other way, and with `evenodd` they are always holes.
+    ///
+    /// The file is flipped so that `y` goes up, with the bottom left corner of its `viewBox` at
+    /// the origin of the mesh. Without a `viewBox`, the origin of the file stays at the origin
+    /// of the mesh.
+    ///
+    /// The scale and the tolerance must be positive numbers.
+    pub fn from_svg(svg: &str, settings: &SvgSettings) -> Result<NavMesh, SvgError> {
+        for (value, name) in [(settings.scale, "scale"), (settings.tolerance, "tolerance")] {
+            if !value.is_finite() || value <= 0.0 {
+                return Err(SvgError::Build(NavMeshBuildError::InvalidSetting(name)));
+            }
+        }
+        let document = Document::parse(svg).map_err(SvgError::Xml)?;
+        let root = document.root_element();
+        let view_box = match root.attribute("viewBox") {
+            Some(view_box) => view_box
+                .parse::<ViewBox>()
+                .map_err(|error| SvgError::InvalidAttribute(format!("viewBox {error}")))?,
+            None => ViewBox::new(0.0, 0.0, 0.0, 0.0),
+        };
+        // SVG coordinates go down
+        let to_mesh = Affine2::from_scale(Vec2::new(settings.scale, -settings.scale))
+            * Affine2::from_translation(-Vec2::new(
+                view_box.x as f32,
+                (view_box.y + view_box.h) as f32,
+            ));
+
+        let mut walkable = vec![];
+        let mut obstacles = vec![];
+        for element in root
+            .descendants()
+            .filter(|node| node.has_tag_name("path") || node.has_tag_name("polygon"))
+            .filter(|node| {
+                !node.ancestors().any(|ancestor| {
+                    NOT_RENDERED.contains(&ancestor.tag_name().name())
+                        || own_property(ancestor, "display") == Some("none")
+                })
+            })
+        {
+            let is_obstacle = element.ancestors().any(|node| {
+                node.attribute("class").is_some_and(|classes| {
+                    classes
+                        .split_whitespace()
+                        .any(|class| class == settings.obstacle_class)
+                })
+            });
+            if !is_obstacle && !is_filled(element) {
+                continue;
+            }
+            let mut transform = to_mesh;
+            for node in element.ancestors().collect::<Vec<_>>().into_iter().rev() {
+                transform *= node_transform(node)?;
+            }
+
+            let rings = rings(element, transform, settings.tolerance)?;
+            let area = if property(element, "fill-rule") == Some("evenodd") {
+                even_odd(rings)
+            } else {
+                nonzero(rings)
+            };
+            if is_obstacle {
+                obstacles.extend(area);
+            } else {
+                walkable.extend(area);
+            }
+        }
+
+        let walkable = union_all(walkable).difference(&union_all(obstacles));
+        let mesh = triangulate(&walkable, 0.0).map_err(SvgError::Build)?;
+        Ok(NavMesh::from_polyanya_mesh(mesh))
+    }
+}
+
+/// The transform of a node, from its `transform` attribute.
+fn node_transform(node: Node) -> Result<Affine2, SvgError> {
+    let Some(transform) = node.attribute("transform") else {
+        return Ok(Affine2::IDENTITY);
+    };
+    let svgtypes::Transform { a, b, c, d, e, f } = transform
+        .parse()
+        .map_err(|error| SvgError::InvalidAttribute(format!("transform {error}")))?;
+    Ok(Affine2::from_cols_array(
+        &[a, b, c, d, e, f].map(|value| value as f32),
+    ))
+}
+
+/// The value of a presentation property of an element, from its style or attribute, or those
+/// of its ancestors.
+fn property<'a>(element: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    element
+        .ancestors()
+        .find_map(|node| own_property(node, name))
+}
+
+/// The value of a presentation property set on a node, from its style or attribute.
+fn own_property<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attribute("style")
+        .and_then(|style| {
+            style
+                .split(';')
+                .filter_map(|declaration| declaration.split_once(':'))
+                .find(|(property, _)| property.trim() == name)
+                .map(|(_, value)| value.trim())
+        })
+        .or(node.attribute(name))
+}
+
+/// Checks if an element is filled, from its `fill` attribute or style, or those of its
+/// ancestors. Elements are filled by default.
+fn is_filled(element: Node) -> bool {
+    property(element, "fill") != Some("none")
+}
+
+/// The area covered by rings with the `evenodd` fill rule: each ring toggles the area it covers.
+fn even_odd(rings: Vec<Vec<Vec2>>) -> MultiPolygon<f32> {
+    rings
+        .into_iter()
+        .filter(|ring| ring.len() > 2)
+        .fold(MultiPolygon(vec![]), |area, ring| {
+            area.xor(&MultiPolygon(vec![to_polygon(&ring)]))
+        })
+}
+
+/// The area covered by rings with the `nonzero` fill rule: each ring adds one to the winding
+/// number of the area it covers, or removes one when it turns the other way, and the area with
+/// a winding number other than zero is covered.
+fn nonzero(rings: Vec<Vec<Vec2>>) -> MultiPolygon<f32> {
+    // Areas by winding number, the rest of the plane being at zero
+    let mut windings: HashMap<i32, MultiPolygon<f32>> = HashMap::new();
+    for ring in rings.into_iter().filter(|ring| ring.len() > 2) {
+        let turn = if signed_area(&ring) >= 0.0 { 1 } else { -1 };
+        let ring = MultiPolygon(vec![to_polygon(&ring)]);
+        let mut next: HashMap<i32, MultiPolygon<f32>> = HashMap::new();
+        let mut add = |winding: i32, area: MultiPolygon<f32>| {
+            if winding != 0 && !area.0.is_empty() {
+                let merged = match next.remove(&winding) {
+                    Some(existing) => existing.union(&area),
+                    None => area,
+                };
+                next.insert(winding, merged);
+            }
+        };
+        let mut outside = ring.clone();
+        for (winding, area) in windings {
+            outside = outside.difference(&area);
+            add(winding + turn, area.intersection(&ring));
+            add(winding, area.difference(&ring));
+        }
+        add(turn, outside);
+        windings = next;
+    }
+    union_all(windings.into_values().flatten())
+}
+
+/// Area of a ring, positive if it turns counter clockwise.
+fn signed_area(ring: &[Vec2]) -> f32 {
+    ring.iter()
+        .zip(ring.iter().cycle().skip(1))
+        .map(|(a, b)| a.perp_dot(*b))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// The closed rings of a shape, in mesh coordinates.
+fn rings(element: Node, transform: Affine2, tolerance: f32) -> Result<Vec<Vec<Vec2>>, SvgError> {
+    let point = |x: f64, y: f64| transform.transform_point2(Vec2::new(x as f32, y as f32));
+    if element.has_tag_name("polygon") {
+        let points = PointsParser::from(element.attribute("points").unwrap_or_default());
+        return Ok(vec![points.map(|(x, y)| point(x, y)).collect()]);
+    }
+
+    let mut rings: Vec<Vec<Vec2>> = vec![];
+    for segment in SimplifyingPathParser::from(element.attribute("d").unwrap_or_default()) {
+        let segment = segment.map_err(|error| SvgError::InvalidAttribute(format!("d {error}")))?;
+        let start = rings.last().and_then(|ring| ring.last()).copied();
+        match (segment, start) {
+            (SimplePathSegment::MoveTo { x, y }, _) => rings.push(vec![point(x, y)]),
+            (SimplePathSegment::LineTo { x, y }, Some(_)) => {
+                rings.last_mut().unwrap().push(point(x, y));
+            }
+            (SimplePathSegment::Quadratic { x1, y1, x, y }, Some(start)) => {
+                let controls = [start, point(x1, y1), point(x, y)];
+                // Distance to the segments is at most an eighth of the largest second derivative
+                // divided by the square of the number of segments
+                let curvature = 2.0 * (controls[0] - 2.0 * controls[1] + controls[2]).length();
+                let segments = segment_count(curvature, tolerance);
+                rings.last_mut().unwrap().extend((1..=segments).map(|i| {
+                    let t = i as f32 / segments as f32;
+                    controls[0]
+                        .lerp(controls[1], t)
+                        .lerp(controls[1].lerp(controls[2], t), t)
+                }));
+            }
+            (
+                SimplePathSegment::CurveTo {
+                    x1,
+                    y1,
+                    x2,
+                    y2,
+                    x,
+                    y,
+                },
+                Some(start),
+            ) => {
+                let controls = [start, point(x1, y1), point(x2, y2), point(x, y)];
+                let curvature = 6.0
+                    * (controls[0] - 2.0 * controls[1] + controls[2])
+                        .length()
+                        .max((controls[1] - 2.0 * controls[2] + controls[3]).length());
+                let segments = segment_count(curvature, tolerance);
+                rings.last_mut().unwrap().extend((1..=segments).map(|i| {
+                    let t = i as f32 / segments as f32;
+                    let [a, b, c] = [0, 1, 2].map(|j| controls[j].lerp(controls[j + 1], t));
+                    a.lerp(b, t).lerp(b.lerp(c, t), t)
+                }));
+            }
+            // Rings are always closed, and a path can't draw before moving
+            _ => {}
+        }
+    }
+    Ok(rings)
+}
+
+/// Number of segments needed to follow a curve within `tolerance`, from the largest second
+/// derivative of the curve.
+fn segment_count(curvature: f32, tolerance: f32) -> usize {
+    ((curvature / (8.0 * tolerance)).sqrt().ceil() as usize).clamp(1, 64)
+}
+
+#[cfg(test)]
+mod tests {
+    use itertools::Itertools;
+
+    use super::*;
+    use crate::islands::neighbour;
+
+    /// A 100 by 80 room, scaled down, with a pillar, a wall and a pit, and a guide line
+    const LEVEL: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 80">
+  <g transform="scale(2)">
+    <path d="M0 0 H50 V40 H0 Z" style="stroke:#000;fill:#fff"/>
+  </g>
+  <path class="obstacle" d="M20 30 a10 10 0 1 0 20 0 a10 10 0 1 0 -20 0 z"/>
+  <g class="walls">
+    <polygon class="obstacle" points="60,0 65,0 65,60 60,60"/>
+  </g>
+  <path fill="red" d="M70 40 h20 v20 h-20 Z M75 45 v10 h10 v-10 Z" class="obstacle"/>
+  <path fill="none" stroke="blue" d="M0 79 H100 V20"/>
+  <g style="display: none">
+    <path class="obstacle" d="M0 0 H10 V10 H0 Z"/>
+  </g>
+  <defs>
+    <path id="marker" d="M0 0 H10 V10 H0 Z" class="obstacle"/>
+  </defs>
+</svg>
+"#;
+
+    #[test]
+    fn shapes_from_svg() {
+        let navmesh = NavMesh::from_svg(
+            LEVEL,
+            &SvgSettings {
+                scale: 0.1,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // The room covers the view box, y going up
+        assert!(navmesh.is_in_mesh(Vec2::new(0.5, 0.5)));
+        assert!(navmesh.is_in_mesh(Vec2::new(9.5, 7.5)));
+        // Shapes in definitions or not displayed are not drawn
+        assert!(navmesh.is_in_mesh(Vec2::new(0.5, 7.5)));
+        // The pillar, a circle centered on (3, 5) in the mesh
+        assert!(!navmesh.is_in_mesh(Vec2::new(3.0, 5.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(3.0, 5.7)));
+        assert!(navmesh.is_in_mesh(Vec2::new(3.75, 5.75)));
+        // The wall, with a passage at the bottom
+        assert!(!navmesh.is_in_mesh(Vec2::new(6.25, 6.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(6.25, 1.0)));
+        // The pit is a ring, with walkable ground in the middle
+        assert!(!navmesh.is_in_mesh(Vec2::new(7.25, 3.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(8.0, 3.0)));
+        assert_eq!(navmesh.island_count(), 2);
+
+        let path = navmesh
+            .path(Vec2::new(1.0, 7.0), Vec2::new(9.0, 7.0))
+            .unwrap();
+        assert!(path.path.iter().any(|point| point.y < 2.1), "{path:?}");
+    }
+
+    #[test]
+    fn rings_follow_fill_rule() {
+        // Two squares turning the same way, the second one inside the first
+        let squares = r#"<svg viewBox="0 0 10 10">
+  <path style="fill-rule:RULE" d="M0 0 H10 V10 H0 Z M3 3 H7 V7 H3 Z"/>
+</svg>"#;
+
+        let navmesh =
+            NavMesh::from_svg(&squares.replace("RULE", "nonzero"), &Default::default()).unwrap();
+        assert!(navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(1.0, 1.0)));
+        let navmesh =
+            NavMesh::from_svg(&squares.replace("RULE", "evenodd"), &Default::default()).unwrap();
+        assert!(!navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(1.0, 1.0)));
+
+        // The inner square turning the other way is a hole either way
+        let reversed = squares.replace("M3 3 H7 V7 H3 Z", "M3 3 V7 H7 V3 Z");
+        let navmesh = NavMesh::from_svg(&reversed, &Default::default()).unwrap();
+        assert!(!navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(1.0, 1.0)));
+
+        // Overlapping rings turning the same way are covered once
+        let overlapping = r#"<svg viewBox="0 0 10 10">
+  <path d="M0 0 H6 V10 H0 Z M4 0 H10 V10 H4 Z"/>
+</svg>"#;
+        let navmesh = NavMesh::from_svg(overlapping, &Default::default()).unwrap();
+        assert!(navmesh.is_in_mesh(Vec2::new(5.0, 5.0)));
+        assert_eq!(navmesh.island_count(), 1);
+    }
+
+    #[test]
+    fn curves_follow_tolerance() {
+        let circle = r#"<svg viewBox="-10 -10 20 20">
+  <path d="M-10 0 C-10 -5.523 -5.523 -10 0 -10 S10 -5.523 10 0 S5.523 10 0 10 S-10 5.523 -10 0"/>
+</svg>"#;
+
+        for tolerance in [0.5, 0.01] {
+            let navmesh = NavMesh::from_svg(
+                circle,
+                &SvgSettings {
+                    tolerance,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+            let mesh = navmesh.get();
+            let vertices = mesh.vertices.len();
+            for vertex in &mesh.vertices {
+                let distance = (vertex.coords - Vec2::splat(10.0)).length();
+                assert!((distance - 10.0).abs() < 0.01, "{}", vertex.coords);
+            }
+            // Middle of the edges on the boundary of the mesh
+            for (index, polygon) in mesh.polygons.iter().enumerate() {
+                for (a, b) in polygon.vertices.iter().circular_tuple_windows() {
+                    if neighbour(&mesh, index, *a, *b).is_some() {
+                        continue;
+                    }
+                    let middle = (mesh.vertices[*a as usize].coords
+                        + mesh.vertices[*b as usize].coords)
+                        / 2.0;
+                    let distance = (middle - Vec2::splat(10.0)).length();
+                    assert!(distance > 10.0 - tolerance - 0.01, "{tolerance}: {middle}");
+                }
+            }
+            assert!(
+                (tolerance > 0.1 && vertices < 24) || (tolerance < 0.1 && vertices > 48),
+                "{tolerance}: {vertices}"
+            );
+        }
+    }
+
+    #[test]
+    fn invalid_settings_are_rejected() {
+        for (settings, name) in [
+            (
+                SvgSettings {
+                    scale: 0.0,
+                    ..Default::default()
+                },
+                "scale",
+            ),
+            (
+                SvgSettings {
+                    tolerance: f32::NAN,
+                    ..Default::default()
+                },
+                "tolerance",
+            ),
+            (
+                SvgSettings {
+                    tolerance: -0.1,
+                    ..Default::default()
+                },
+                "tolerance",
+            ),
+        ] {
+            assert!(matches!(
+                NavMesh::from_svg(LEVEL, &settings),
+                Err(SvgError::Build(NavMeshBuildError::InvalidSetting(invalid))) if invalid == name
+            ));
+        }
+    }
+}
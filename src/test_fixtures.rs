@@ -0,0 +1,29 @@
+//! Meshes shared by the tests of several modules
+
+use bevy::prelude::*;
+
+use crate::{builder::NavMeshBuilder, NavMesh};
+
+/// The corners of an axis aligned rectangle, counter clockwise.
+pub(crate) fn square(from: Vec2, to: Vec2) -> Vec<Vec2> {
+    vec![from, Vec2::new(to.x, from.y), to, Vec2::new(from.x, to.y)]
+}
+
+/// A 10 by 10 square, made of two triangles.
+pub(crate) fn square_navmesh() -> NavMesh {
+    NavMesh::from_polyanya_mesh(
+        polyanya::Trimesh {
+            vertices: square(Vec2::ZERO, Vec2::splat(10.)),
+            triangles: vec![[0, 1, 2], [0, 2, 3]],
+        }
+        .into(),
+    )
+}
+
+/// A 10 by 10 square with a rectangular obstacle from `min` to `max`.
+pub(crate) fn square_with_obstacle(min: Vec2, max: Vec2) -> NavMesh {
+    NavMeshBuilder::new(square(Vec2::ZERO, Vec2::splat(10.)))
+        .with_obstacle(square(min, max))
+        .build()
+        .unwrap()
+}
@@ -0,0 +1,376 @@
+//! Build a [`NavMesh`] from the objects of a map made with [Tiled](https://www.mapeditor.org)
+
+use std::{error::Error, f32::consts::TAU, fmt::Display};
+
+use bevy::prelude::*;
+use geo::{BooleanOps, MultiPolygon};
+use roxmltree::{Document, Node};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    builder::{to_polygon, triangulate, union_all},
+    NavMesh, NavMeshBuildError,
+};
+
+/// Number of segments used to approximate an ellipse.
+const ELLIPSE_SEGMENTS: usize = 32;
+
+/// Settings for [`NavMesh::from_tiled_map`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TiledSettings {
+    /// Name of the object layer to use. Default is `navmesh`.
+    pub layer: String,
+    /// Class of the objects that are walkable regions. Default is `walkable`.
+    ///
+    /// If the layer has no walkable region, the whole map is walkable.
+    pub walkable_class: String,
+    /// Class of the objects that are obstacles. Default is `obstacle`.
+    pub obstacle_class: String,
+    /// Size of a pixel of the map in mesh coordinates. Default is `1.0`.
+    pub scale: f32,
+}
+
+impl Default for TiledSettings {
+    fn default() -> Self {
+        Self {
+            layer: "navmesh".to_string(),
+            walkable_class: "walkable".to_string(),
+            obstacle_class: "obstacle".to_string(),
+            scale: 1.0,
+        }
+    }
+}
+
+/// Error that can happen while reading a Tiled map
+#[derive(Debug)]
+pub enum TiledMapError {
+    /// The map is not a valid XML file
+    Xml(roxmltree::Error),
+    /// The object layer was not found in the map
+    LayerNotFound(String),
+    /// An attribute is missing or has an invalid value
+    InvalidAttribute(String),
+    /// An object of the layer uses a template, from a file that is not read
+    Template(String),
+    /// Error when building the `NavMesh` from the objects
+    Build(NavMeshBuildError),
+}
+
+impl Display for TiledMapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TiledMapError::Xml(xml_error) => write!(f, "XML error: {}", xml_error),
+            TiledMapError::LayerNotFound(name) => write!(f, "object layer {} not found", name),
+            TiledMapError::InvalidAttribute(reason) => write!(f, "invalid attribute: {}", reason),
+            TiledMapError::Template(template) => {
+                write!(f, "object templates are not supported: {}", template)
+            }
+            TiledMapError::Build(build_error) => {
+                write!(f, "NavMesh build error: {}", build_error)
+            }
+        }
+    }
+}
+
+impl Error for TiledMapError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            TiledMapError::Xml(xml_error) => Some(xml_error),
+            TiledMapError::LayerNotFound(_)
+            | TiledMapError::InvalidAttribute(_)
+            | TiledMapError::Template(_) => None,
+            TiledMapError::Build(build_error) => Some(build_error),
+        }
+    }
+}
+
+impl NavMesh {
+    /// Creates a [`NavMesh`] from an object layer of a Tiled map in the `.tmx` format.
+    ///
+    /// Rectangles, ellipses and polygons are used, their class (or their type before Tiled 1.9)
+    /// telling if they are walkable regions or obstacles. Other objects and hidden objects are
+    /// ignored. The map is flipped so that `y` goes up, with its bottom left corner at the origin
+    /// of the mesh.
+    ///
+    /// The offsets of the layer and of the groups containing it are applied. Objects using a
+    /// template are rejected, as templates are stored in other files. The scale must be a
+    /// positive number.
+    pub fn from_tiled_map(map: &str, settings: &TiledSettings) -> Result<NavMesh, TiledMapError> {
+        if !settings.scale.is_finite() || settings.scale <= 0.0 {
+            return Err(TiledMapError::Build(NavMeshBuildError::InvalidSetting(
+                "scale",
+            )));
+        }
+        let document = Document::parse(map).map_err(TiledMapError::Xml)?;
+        let map = document.root_element();
+        let size = Vec2::new(
+            number(map, "width")? * number(map, "tilewidth")?,
+            number(map, "height")? * number(map, "tileheight")?,
+        );
+        let layer = map
+            .descendants()
+            .find(|node| {
+                node.has_tag_name("objectgroup") && node.attribute("name") == Some(&settings.layer)
+            })
+            .ok_or_else(|| TiledMapError::LayerNotFound(settings.layer.clone()))?;
+        // Offsets of groups add up with the offset of the layer
+        let offset = layer
+            .ancestors()
+            .take_while(|node| *node != map)
+            .map(|node| {
+                Ok(Vec2::new(
+                    optional_number(node, "offsetx")?,
+                    optional_number(node, "offsety")?,
+                ))
+            })
+            .sum::<Result<Vec2, _>>()?;
+        // Tiled coordinates are in pixels, with `y` going down
+        let to_mesh = |point: Vec2| Vec2::new(point.x, size.y - point.y) * settings.scale;
+
+        let mut walkable = vec![];
+        let mut obstacles = vec![];
+        for object in layer
+            .children()
+            .filter(|node| node.has_tag_name("object") && node.attribute("visible") != Some("0"))
+        {
+            if let Some(template) = object.attribute("template") {
+                return Err(TiledMapError::Template(template.to_string()));
+            }
+            let class = object
+                .attribute("class")
+                .or(object.attribute("type"))
+                .unwrap_or_default();
+            let polygons = if class == settings.walkable_class {
+                &mut walkable
+            } else if class == settings.obstacle_class {
+                &mut obstacles
+            } else {
+                continue;
+            };
+            let Some(shape) = shape(object)? else {
+                continue;
+            };
+            // Objects rotate clockwise around their position
+            let position = Vec2::new(number(object, "x")?, number(object, "y")?) + offset;
+            let rotation = Mat2::from_angle(optional_number(object, "rotation")?.to_radians());
+            polygons.push(to_polygon(
+                &shape
+                    .into_iter()
+                    .map(|point| to_mesh(position + rotation * point))
+                    .collect::<Vec<_>>(),
+            ));
+        }
+
+        let walkable = if walkable.is_empty() {
+            MultiPolygon(vec![to_polygon(&[
+                to_mesh(Vec2::new(0.0, size.y)),
+                to_mesh(size),
+                to_mesh(Vec2::new(size.x, 0.0)),
+                to_mesh(Vec2::ZERO),
+            ])])
+        } else {
+            union_all(walkable)
+        };
+        let mesh = triangulate(&walkable.difference(&union_all(obstacles)), 0.0)
+            .map_err(TiledMapError::Build)?;
+        Ok(NavMesh::from_polyanya_mesh(mesh))
+    }
+}
+
+/// Points of the shape of an object, relative to its position, or `None` for objects without
+/// an area like points and polylines.
+fn shape(object: Node) -> Result<Option<Vec<Vec2>>, TiledMapError> {
+    let size = Vec2::new(
+        optional_number(object, "width")?,
+        optional_number(object, "height")?,
+    );
+    let Some(kind) = object.children().find(Node::is_element) else {
+        // Rectangles have no child, but tiles are also objects without a child
+        if object.has_attribute("gid") || size.cmple(Vec2::ZERO).any() {
+            return Ok(None);
+        }
+        return Ok(Some(vec![
+            Vec2::ZERO,
+            Vec2::new(size.x, 0.0),
+            size,
+            Vec2::new(0.0, size.y),
+        ]));
+    };
+    match kind.tag_name().name() {
+        "polygon" => kind
+            .attribute("points")
+            .unwrap_or_default()
+            .split_whitespace()
+            .map(|point| {
+                point
+                    .split_once(',')
+                    .and_then(|(x, y)| Some(Vec2::new(x.parse().ok()?, y.parse().ok()?)))
+                    .ok_or_else(|| {
+                        TiledMapError::InvalidAttribute(format!("polygon point {point}"))
+                    })
+            })
+            .collect::<Result<_, _>>()
+            .map(Some),
+        "ellipse" if size.cmpgt(Vec2::ZERO).all() => Ok(Some(
+            (0..ELLIPSE_SEGMENTS)
+                .map(|i| {
+                    let direction = Vec2::from_angle(i as f32 * TAU / ELLIPSE_SEGMENTS as f32);
+                    (direction + Vec2::ONE) * size / 2.0
+                })
+                .collect(),
+        )),
+        _ => Ok(None),
+    }
+}
+
+/// A number from an attribute of a node.
+fn number(node: Node, attribute: &str) -> Result<f32, TiledMapError> {
+    let value = node.attribute(attribute).ok_or_else(|| {
+        TiledMapError::InvalidAttribute(format!(
+            "missing attribute {attribute} of {}",
+            node.tag_name().name()
+        ))
+    })?;
+    value
+        .parse()
+        .map_err(|_| TiledMapError::InvalidAttribute(format!("attribute {attribute} is {value}")))
+}
+
+/// A number from an attribute of a node, or `0.0` if it's not set.
+fn optional_number(node: Node, attribute: &str) -> Result<f32, TiledMapError> {
+    if node.has_attribute(attribute) {
+        number(node, attribute)
+    } else {
+        Ok(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A map of 10 by 8 tiles of 16 pixels, with a rotated square, a round pillar and a wall
+    const MAP: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="10" height="8" tilewidth="16" tileheight="16">
+ <group id="1" name="level" offsetx="8" offsety="4">
+  <objectgroup id="2" name="navmesh" offsetx="-8" offsety="-4">
+   <object id="1" class="obstacle" x="32" y="16" width="16" height="16" rotation="45"/>
+   <object id="2" type="obstacle" x="96" y="64" width="32" height="32">
+    <ellipse/>
+   </object>
+   <object id="3" class="obstacle" x="128" y="0">
+    <polygon points="0,0 8,0 8,96 0,96"/>
+   </object>
+   <object id="4" class="decoration" x="0" y="96" width="160" height="32"/>
+   <object id="5" class="obstacle" x="16" y="112">
+    <polyline points="0,0 64,0"/>
+   </object>
+  </objectgroup>
+ </group>
+</map>
+"#;
+
+    fn settings() -> TiledSettings {
+        TiledSettings {
+            scale: 1.0 / 16.0,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn objects_are_obstacles() {
+        let navmesh = NavMesh::from_tiled_map(MAP, &settings()).unwrap();
+
+        // The square turned around its top left corner, now a diamond below it
+        assert!(!navmesh.is_in_mesh(Vec2::new(2.0, 6.3)));
+        assert!(navmesh.is_in_mesh(Vec2::new(2.6, 6.8)));
+        assert!(navmesh.is_in_mesh(Vec2::new(1.4, 6.8)));
+        // The pillar
+        assert!(!navmesh.is_in_mesh(Vec2::new(7.0, 3.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(6.2, 3.8)));
+        // The wall, leaving a gap at the bottom
+        assert!(!navmesh.is_in_mesh(Vec2::new(8.25, 5.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(8.25, 1.0)));
+        // Ignored objects
+        assert!(navmesh.is_in_mesh(Vec2::new(3.0, 1.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(9.0, 7.0)));
+
+        let path = navmesh
+            .path(Vec2::new(7.0, 7.0), Vec2::new(9.0, 7.0))
+            .unwrap();
+        assert!(path.path.iter().any(|point| point.y < 2.1), "{path:?}");
+    }
+
+    #[test]
+    fn walkable_regions() {
+        let map = MAP.replace("decoration", "walkable");
+        let navmesh = NavMesh::from_tiled_map(&map, &settings()).unwrap();
+
+        assert!(navmesh.is_in_mesh(Vec2::new(3.0, 1.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(8.25, 1.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(3.0, 3.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(9.0, 7.0)));
+
+        let missing = NavMesh::from_tiled_map(
+            &map,
+            &TiledSettings {
+                layer: "collisions".to_string(),
+                ..settings()
+            },
+        );
+        assert!(matches!(missing, Err(TiledMapError::LayerNotFound(_))));
+    }
+
+    #[test]
+    fn offsets_of_groups_add_up() {
+        let map = MAP.replace(r#"offsetx="-8" offsety="-4""#, "");
+        let navmesh = NavMesh::from_tiled_map(&map, &settings()).unwrap();
+
+        // The pillar moved half a tile right and a quarter tile down
+        assert!(navmesh.is_in_mesh(Vec2::new(6.2, 3.8)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(7.5, 2.0)));
+        assert!(navmesh.is_in_mesh(Vec2::new(6.6, 3.55)));
+    }
+
+    #[test]
+    fn templates_are_rejected() {
+        let map = MAP.replace(
+            r#"<object id="4" class="decoration""#,
+            r#"<object id="4" template="wall.tx""#,
+        );
+        assert!(matches!(
+            NavMesh::from_tiled_map(&map, &settings()),
+            Err(TiledMapError::Template(template)) if template == "wall.tx"
+        ));
+    }
+
+    #[test]
+    fn hidden_objects_are_ignored() {
+        let map = MAP.replace(
+            r#"<object id="2" type="obstacle""#,
+            r#"<object id="2" type="obstacle" visible="0""#,
+        );
+        let navmesh = NavMesh::from_tiled_map(&map, &settings()).unwrap();
+
+        assert!(navmesh.is_in_mesh(Vec2::new(7.0, 3.0)));
+        assert!(!navmesh.is_in_mesh(Vec2::new(8.25, 5.0)));
+    }
+
+    #[test]
+    fn scale_must_be_positive() {
+        for scale in [0.0, -1.0, f32::NAN] {
+            assert!(matches!(
+                NavMesh::from_tiled_map(
+                    MAP,
+                    &TiledSettings {
+                        scale,
+                        ..settings()
+                    }
+                ),
+                Err(TiledMapError::Build(NavMeshBuildError::InvalidSetting(
+                    "scale"
+                )))
+            ));
+        }
+    }
+}